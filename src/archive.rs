@@ -0,0 +1,231 @@
+use std::{
+    collections::HashSet,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use anyhow::Result;
+use url::Url;
+
+use crate::{
+    config::get_app_dir,
+    downloader::{DownloadInfo, PlaylistEntry},
+};
+
+lazy_static::lazy_static! {
+    /// Keys of `~/.ugdown/archive.txt`, loaded on first use.
+    static ref ARCHIVE: RwLock<Option<HashSet<String>>> = RwLock::new(None);
+}
+
+/// Query parameters which don't change the video an url points to.
+const TRACKING_PARAMS: [&str; 9] = [
+    "feature",
+    "si",
+    "pp",
+    "t",
+    "spm_id_from",
+    "vd_source",
+    "share_source",
+    "fbclid",
+    "gclid",
+];
+
+fn get_archive_file() -> Result<PathBuf> {
+    Ok(get_app_dir()?.join("archive.txt"))
+}
+
+/// Gets id of a youtube video url, which youtube-dl archives as `youtube <id>`.
+fn get_youtube_id(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    let mut segments = url.path_segments()?.filter(|x| x.len() > 0);
+    if host == "youtu.be" {
+        return segments.next().map(|x| x.to_owned());
+    }
+    if host != "youtube.com" && host.ends_with(".youtube.com") == false {
+        return None;
+    }
+
+    match segments.next() {
+        Some("watch") => url
+            .query_pairs()
+            .find(|(key, _)| key == "v")
+            .map(|(_, value)| value.to_string()),
+        Some("shorts" | "live" | "embed") => segments.next().map(|x| x.to_owned()),
+        _ => None,
+    }
+}
+
+/// Normalizes `url` so that its variants (scheme, `www.` or `m.` host, tracking
+/// parameters, fragment and trailing slash) share one key.
+fn normalize_url(url: &str) -> String {
+    let url = match Url::parse(url.trim()) {
+        Ok(url) => url,
+        Err(_) => return url.trim().to_owned(),
+    };
+
+    let host = url.host_str().unwrap_or("").to_lowercase();
+    let host = host
+        .strip_prefix("www.")
+        .or(host.strip_prefix("m."))
+        .unwrap_or(&host);
+    let mut query: Vec<String> = url
+        .query_pairs()
+        .filter(|(key, _)| {
+            key.starts_with("utm_") == false && TRACKING_PARAMS.contains(&key.as_ref()) == false
+        })
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    query.sort();
+
+    let mut result = format!("{}{}", host, url.path().trim_end_matches('/'));
+    if query.len() > 0 {
+        result = format!("{}?{}", result, query.join("&"));
+    }
+    result
+}
+
+/// Gets archive key of a url before detecting, `youtube <id>` for youtube videos
+/// and `url <normalized url>` for the others.
+pub fn get_url_key(url: &str) -> String {
+    match Url::parse(url.trim()).ok().and_then(|x| get_youtube_id(&x)) {
+        Some(id) => format!("youtube {}", id),
+        None => format!("url {}", normalize_url(url)),
+    }
+}
+
+/// Gets archive key of a detected stream, the extractor and id reported by
+/// engine if any, falling back to its url.
+pub fn get_archive_key(info: &DownloadInfo) -> String {
+    info.archive_id
+        .clone()
+        .unwrap_or_else(|| get_url_key(&info.url))
+}
+
+fn parse_archive(content: &str) -> impl Iterator<Item = String> + '_ {
+    content
+        .lines()
+        .map(|x| x.trim())
+        .filter(|x| x.len() > 0 && x.starts_with('#') == false)
+        .map(|x| x.to_owned())
+}
+
+/// Runs `f` with the loaded archive keys.
+fn with_archive<T>(f: impl FnOnce(&mut HashSet<String>) -> T) -> T {
+    let mut archive = ARCHIVE.write().unwrap();
+    let keys = archive.get_or_insert_with(|| {
+        get_archive_file()
+            .and_then(|x| Ok(std::fs::read_to_string(x)?))
+            .map(|x| parse_archive(&x).collect())
+            .unwrap_or_default()
+    });
+    f(keys)
+}
+
+pub fn is_url_archived(url: &str) -> bool {
+    let key = get_url_key(url);
+    with_archive(|keys| keys.contains(&key))
+}
+
+fn contains_any(url: &str, archive_id: Option<&String>) -> bool {
+    let url_key = get_url_key(url);
+    with_archive(|keys| {
+        keys.contains(&url_key) || archive_id.map(|x| keys.contains(x)).unwrap_or(false)
+    })
+}
+
+/// Checks both the engine reported identity and the url of `info`.
+pub fn is_archived(info: &DownloadInfo) -> bool {
+    contains_any(&info.url, info.archive_id.as_ref())
+}
+
+pub fn is_entry_archived(entry: &PlaylistEntry) -> bool {
+    contains_any(&entry.url, entry.archive_id.as_ref())
+}
+
+/// Records a finished download, returns false if it was there already.
+pub fn add_to_archive(info: &DownloadInfo) -> Result<bool> {
+    let key = get_archive_key(info);
+    with_archive(|keys| {
+        if keys.insert(key.clone()) == false {
+            return Ok(false);
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(get_archive_file()?)?;
+        writeln!(file, "{}", key)?;
+        Ok(true)
+    })
+}
+
+/// Merges a youtube-dl `--download-archive` file, returns the number of new keys.
+pub fn import_archive(path: &Path) -> Result<usize> {
+    let content = std::fs::read_to_string(path)?;
+    with_archive(|keys| {
+        let new_keys: Vec<String> = parse_archive(&content)
+            .filter(|x| keys.insert(x.clone()))
+            .collect();
+        if new_keys.len() > 0 {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(get_archive_file()?)?;
+            writeln!(file, "{}", new_keys.join("\n"))?;
+        }
+        Ok(new_keys.len())
+    })
+}
+
+fn is_exportable(key: &str) -> bool {
+    key.starts_with("url ") == false
+}
+
+/// Writes the keys youtube-dl understands into a `--download-archive` file, returns
+/// their number. `url <normalized url>` keys are ugdown's own and left out.
+pub fn export_archive(path: &Path) -> Result<usize> {
+    with_archive(|keys| {
+        let mut lines: Vec<&String> = keys.iter().filter(|x| is_exportable(x)).collect();
+        lines.sort();
+        let content: String = lines.iter().map(|x| format!("{}\n", x)).collect();
+        std::fs::write(path, content)?;
+        Ok(lines.len())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_key() {
+        for url in [
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&feature=share",
+            "https://youtu.be/dQw4w9WgXcQ?si=abc",
+            "https://m.youtube.com/shorts/dQw4w9WgXcQ",
+        ] {
+            assert_eq!(get_url_key(url), "youtube dQw4w9WgXcQ");
+        }
+
+        assert_eq!(
+            get_url_key("https://www.bilibili.com/video/BV1xx/?spm_id_from=333&p=2#reply"),
+            "url bilibili.com/video/BV1xx?p=2"
+        );
+        assert_eq!(
+            get_url_key("http://m.bilibili.com/video/BV1xx?p=2&utm_source=x"),
+            get_url_key("https://www.bilibili.com/video/BV1xx/?p=2")
+        );
+        assert_ne!(
+            get_url_key("https://www.bilibili.com/video/BV1xx?p=1"),
+            get_url_key("https://www.bilibili.com/video/BV1xx?p=2")
+        );
+    }
+
+    #[test]
+    fn test_is_exportable() {
+        assert!(is_exportable(&get_url_key("https://youtu.be/dQw4w9WgXcQ")));
+        assert!(is_exportable("bilibili BV1xx_p2"));
+        assert!(is_exportable(&get_url_key("https://vimeo.com/1")) == false);
+    }
+}
@@ -0,0 +1,253 @@
+use std::{collections::HashMap, path::PathBuf, sync::RwLock};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+lazy_static::lazy_static! {
+    static ref CONFIG: RwLock<Config> = RwLock::new(load_config().unwrap_or_default());
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub output_dir: Option<String>,
+    pub check_engine_update: bool,
+    pub python_path: Option<String>,
+    pub pip_index_url: Option<String>,
+    pub pip_find_links: Option<String>,
+    pub pinned_versions: HashMap<String, String>,
+    pub mirrors: Vec<Mirror>,
+    pub github_mirror: String,
+    pub github_token: Option<String>,
+    /// Download direct part urls with aria2c instead of the engine.
+    pub use_aria2: bool,
+    pub aria2_connections: u32,
+    /// Engines tried in order by the "Auto" engine.
+    pub engine_fallback: Vec<String>,
+    pub rules: Vec<DomainRule>,
+    /// Default preference expression to pre-select streams, e.g. `best[height<=1080] / best`.
+    pub stream_preference: Option<String>,
+    /// Number of urls detected at the same time in batch.
+    pub batch_workers: usize,
+    /// Offers to add urls of supported sites copied to clipboard.
+    pub watch_clipboard: bool,
+    /// Skips videos in download archive when queueing from batch or API.
+    pub skip_archived: bool,
+    /// Minutes between checks of each subscription while ugdown runs.
+    pub subscription_interval: u64,
+    /// Tasks started at the same time, 0 for no limit.
+    pub max_running_tasks: usize,
+    /// Downloads run only in this window, e.g. `23:00-07:00`, and pause outside it.
+    pub run_window: Option<String>,
+    /// Global download cap in bytes per second, unlimited when unset.
+    pub speed_limit: Option<u64>,
+    pub proxy: ProxyConfig,
+    /// Serves the local HTTP API on `127.0.0.1:api_port`.
+    pub api_enabled: bool,
+    pub api_port: u16,
+    pub api_token: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            output_dir: None,
+            check_engine_update: true,
+            python_path: None,
+            pip_index_url: None,
+            pip_find_links: None,
+            pinned_versions: HashMap::new(),
+            mirrors: Mirror::defaults(),
+            github_mirror: "github.com".to_owned(),
+            github_token: None,
+            use_aria2: false,
+            aria2_connections: 8,
+            engine_fallback: ["yt-dlp", "lux", "you-get"].map(|x| x.to_owned()).to_vec(),
+            rules: Vec::new(),
+            stream_preference: None,
+            batch_workers: 3,
+            watch_clipboard: false,
+            skip_archived: true,
+            subscription_interval: 60,
+            max_running_tasks: 0,
+            run_window: None,
+            speed_limit: None,
+            proxy: ProxyConfig::default(),
+            api_enabled: false,
+            api_port: 17380,
+            api_token: None,
+        }
+    }
+}
+
+/// A mirror of GitHub releases.
+///
+/// `download_template` rewrites asset urls, where `{url}` is the original url and
+/// `{path}` is the part after `https://github.com/`. `api_base` replaces
+/// `https://api.github.com`, e.g. for GitHub Enterprise or Gitea.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Mirror {
+    pub name: String,
+    pub download_template: String,
+    pub api_base: Option<String>,
+}
+
+impl Mirror {
+    fn defaults() -> Vec<Self> {
+        [
+            ("github.com", "{url}"),
+            ("ghproxy.com", "https://ghproxy.com/{url}"),
+            ("kgithub.com", "https://kgithub.com/{path}"),
+        ]
+        .map(|(name, download_template)| Self {
+            name: name.to_owned(),
+            download_template: download_template.to_owned(),
+            api_base: None,
+        })
+        .to_vec()
+    }
+
+    /// Parses `name template [api_base]`.
+    pub fn from_line(line: &str) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        let name = parts.next()?.to_owned();
+        let download_template = parts.next()?.to_owned();
+        let api_base = parts.next().map(|x| x.to_owned());
+        Some(Self {
+            name,
+            download_template,
+            api_base,
+        })
+    }
+
+    pub fn to_line(&self) -> String {
+        match &self.api_base {
+            Some(api_base) => format!("{} {} {}", self.name, self.download_template, api_base),
+            None => format!("{} {}", self.name, self.download_template),
+        }
+    }
+}
+
+/// Proxy of engines and HTTP calls.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxyConfig {
+    pub enabled: bool,
+    /// `http://host:port` or `socks5://host:port`.
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Hosts connected directly, in the same form as domain rule patterns.
+    pub bypass: Vec<String>,
+}
+
+/// Per-site defaults, applied when an url with matching host is added.
+///
+/// `pattern` is a host glob like `*.bilibili.com` or `youtu.be`, or a regex on
+/// the whole url when prefixed with `re:`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DomainRule {
+    pub pattern: String,
+    pub engine: Option<String>,
+    pub stream: Option<String>,
+    pub cookie_file: Option<String>,
+    pub output_dir: Option<String>,
+    pub file_name: Option<String>,
+}
+
+impl DomainRule {
+    /// Parses `pattern | engine | stream | cookie_file | output_dir | file_name`,
+    /// where empty or trailing fields are left unset, and `\|` is a `|` in a field.
+    pub fn from_line(line: &str) -> Option<Self> {
+        let mut parts = split_fields(line).into_iter().map(|x| match x.trim() {
+            "" => None,
+            x => Some(x.to_owned()),
+        });
+        Some(Self {
+            pattern: parts.next()??,
+            engine: parts.next().flatten(),
+            stream: parts.next().flatten(),
+            cookie_file: parts.next().flatten(),
+            output_dir: parts.next().flatten(),
+            file_name: parts.next().flatten(),
+        })
+    }
+
+    pub fn to_line(&self) -> String {
+        let mut parts: Vec<String> = [
+            &self.engine,
+            &self.stream,
+            &self.cookie_file,
+            &self.output_dir,
+            &self.file_name,
+        ]
+        .iter()
+        .map(|x| x.as_deref().unwrap_or("").replace('|', "\\|"))
+        .collect();
+        while parts.last().map(|x| x.is_empty()) == Some(true) {
+            parts.pop();
+        }
+        parts.insert(0, self.pattern.replace('|', "\\|"));
+        parts.join(" | ")
+    }
+}
+
+/// Splits at `|` unless escaped as `\|`, e.g. alternation of a regex pattern.
+fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                chars.next();
+                fields.last_mut().unwrap().push('|');
+            }
+            '|' => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+impl Config {
+    pub fn get_mirror(&self) -> Option<&Mirror> {
+        self.mirrors.iter().find(|x| x.name == self.github_mirror)
+    }
+}
+
+pub fn get_config() -> Config {
+    CONFIG.read().unwrap().clone()
+}
+
+pub fn set_config(config: Config) -> Result<()> {
+    save_config(&config)?;
+    *CONFIG.write().unwrap() = config;
+    Ok(())
+}
+
+pub fn get_app_dir() -> Result<PathBuf> {
+    if let Some(user_dir) = directories::UserDirs::new() {
+        let path = user_dir.home_dir().join(".ugdown");
+        if path.is_dir() == false {
+            let _ = std::fs::create_dir_all(&path);
+        }
+        return Ok(path);
+    }
+    Err(anyhow::anyhow!("Unable to locate app dir"))
+}
+
+fn get_config_file() -> Result<PathBuf> {
+    Ok(get_app_dir()?.join("config.json"))
+}
+
+fn load_config() -> Result<Config> {
+    let content = std::fs::read_to_string(get_config_file()?)?;
+    let config: Config = serde_json::from_str(&content)?;
+    Ok(config)
+}
+
+fn save_config(config: &Config) -> Result<()> {
+    let content = serde_json::to_string_pretty(config)?;
+    std::fs::write(get_config_file()?, content)?;
+    Ok(())
+}
@@ -0,0 +1,329 @@
+use std::{
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::{Child, Stdio},
+    time::Duration,
+};
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::proxy::get_proxy_envs;
+use super::*;
+
+/// A private aria2c process controlled through its JSON-RPC interface.
+pub struct Aria2Rpc {
+    child: Child,
+    endpoint: String,
+    token: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Aria2Status {
+    pub status: String,
+    #[serde(deserialize_with = "from_str_number")]
+    pub total_length: u64,
+    #[serde(deserialize_with = "from_str_number")]
+    pub completed_length: u64,
+    #[serde(deserialize_with = "from_str_number")]
+    pub download_speed: u64,
+    pub error_message: Option<String>,
+}
+
+/// aria2 reports all numbers as strings.
+fn from_str_number<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    value.parse().map_err(serde::de::Error::custom)
+}
+
+impl Aria2Rpc {
+    /// aria2c takes proxy of `url` from environment, http proxies only.
+    pub fn spawn(
+        connections: u32,
+        rate_limit: Option<u64>,
+        url: &str,
+        cookie_file: Option<&Path>,
+    ) -> Result<Self> {
+        let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+        let token = uuid::Uuid::new_v4().to_string();
+
+        let mut command = create_hide_window_command("aria2c");
+        if let Some(cookie_file) = cookie_file {
+            command.arg(format!("--load-cookies={}", cookie_file.to_string_lossy()));
+        }
+        let child = command
+            .arg("--enable-rpc")
+            .arg(format!("--max-overall-download-limit={}", rate_limit.unwrap_or(0)))
+            .arg("--rpc-listen-all=false")
+            .arg(format!("--rpc-listen-port={}", port))
+            .arg(format!("--rpc-secret={}", token))
+            .arg(format!("--max-connection-per-server={}", connections))
+            .arg(format!("--split={}", connections))
+            .arg("--auto-file-renaming=false")
+            .arg("--allow-overwrite=true")
+            .envs(get_proxy_envs(url))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|_| anyhow::anyhow!("aria2c is not found, please install it"))?;
+
+        let rpc = Self {
+            child,
+            endpoint: format!("http://127.0.0.1:{}/jsonrpc", port),
+            token,
+        };
+
+        // Wait for the rpc server to come up
+        for _ in 0..50 {
+            if rpc.call("aria2.getVersion", Vec::new()).is_ok() {
+                return Ok(rpc);
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        Err(anyhow::anyhow!("aria2c rpc server does not respond"))
+    }
+
+    fn call(&self, method: &str, params: Vec<Value>) -> Result<Value> {
+        let mut params = params;
+        params.insert(0, json!(format!("token:{}", self.token)));
+
+        let response: Value = ureq::post(&self.endpoint)
+            .timeout(Duration::from_secs(5))
+            .send_json(json!({
+                "jsonrpc": "2.0",
+                "id": "ugdown",
+                "method": method,
+                "params": params,
+            }))
+            .or_else(|error| match error {
+                // aria2 answers rpc errors with http 400
+                ureq::Error::Status(_, response) => Ok(response),
+                error => Err(error),
+            })?
+            .into_json()?;
+
+        if let Some(message) = response["error"]["message"].as_str() {
+            return Err(anyhow::anyhow!("aria2: {}", message));
+        }
+
+        Ok(response["result"].clone())
+    }
+
+    pub fn add_uri(&self, url: &str, dir: &Path, out: &str, headers: &[String]) -> Result<String> {
+        let result = self.call(
+            "aria2.addUri",
+            vec![
+                json!([url]),
+                json!({
+                    "dir": dir,
+                    "out": out,
+                    "header": headers,
+                }),
+            ],
+        )?;
+
+        result
+            .as_str()
+            .map(|x| x.to_owned())
+            .ok_or_else(|| anyhow::anyhow!("aria2 returns no gid"))
+    }
+
+    pub fn tell_status(&self, gid: &str) -> Result<Aria2Status> {
+        let result = self.call(
+            "aria2.tellStatus",
+            vec![
+                json!(gid),
+                json!([
+                    "status",
+                    "totalLength",
+                    "completedLength",
+                    "downloadSpeed",
+                    "errorMessage"
+                ]),
+            ],
+        )?;
+        Ok(serde_json::from_value(result)?)
+    }
+}
+
+impl Drop for Aria2Rpc {
+    fn drop(&mut self) {
+        let _ = self.call("aria2.forceShutdown", Vec::new());
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Downloads all parts of `download_info` with aria2c, then joins them into one file.
+///
+/// `on_progress` receives completed bytes, total bytes and speed, and returns false to stop.
+pub fn download_parts(
+    download_info: &DownloadInfo,
+    connections: u32,
+    mut on_progress: impl FnMut(u64, u64, u64) -> bool,
+) -> Result<PathBuf> {
+    let (output_dir, output_name) = get_output(download_info);
+    let headers = get_part_headers(download_info);
+
+    let rate_limit = get_process_limit(download_info.speed_limit);
+    // Cookie files can't be sent as header, aria2c loads them instead
+    let cookie_file = download_info
+        .cookies
+        .as_ref()
+        .filter(|x| is_cookie_file(x))
+        .map(|x| store_cookies(x))
+        .transpose()?;
+    let rpc = Aria2Rpc::spawn(connections, rate_limit, &download_info.url, cookie_file.as_deref());
+    // aria2c has loaded the cookies by the time its rpc server is up
+    if let Some(cookie_file) = cookie_file {
+        let _ = std::fs::remove_file(cookie_file);
+    }
+    let rpc = rpc?;
+
+    let mut part_files = Vec::new();
+    let mut gids = Vec::new();
+    for (i, part) in download_info.parts.iter().enumerate() {
+        let part_name = with_ext(&format!("{}[{}]", output_name, i), &part.ext);
+        gids.push(rpc.add_uri(&part.url, &output_dir, &part_name, &headers)?);
+        part_files.push(output_dir.join(part_name));
+    }
+    wait_parts(&rpc, &gids, &mut on_progress)?;
+    drop(rpc);
+
+    let output = output_dir.join(with_ext(&output_name, &download_info.ext));
+    merge_parts(&part_files, &output, download_info.need_mux)?;
+    Ok(output)
+}
+
+/// Polls the parts until all complete, failing on the first error or removed one.
+fn wait_parts(
+    rpc: &Aria2Rpc,
+    gids: &[String],
+    on_progress: &mut impl FnMut(u64, u64, u64) -> bool,
+) -> Result<()> {
+    loop {
+        std::thread::sleep(Duration::from_millis(500));
+
+        let status = gids
+            .iter()
+            .map(|gid| rpc.tell_status(gid))
+            .collect::<Result<Vec<Aria2Status>>>()?;
+
+        if let Some(error) = status.iter().find(|x| x.status == "error") {
+            return Err(anyhow::anyhow!(
+                "aria2: {}",
+                error.error_message.clone().unwrap_or("Unknown error".to_owned())
+            ));
+        }
+        // Removed parts never complete
+        if status.iter().any(|x| x.status == "removed") {
+            return Err(anyhow::anyhow!("aria2: download is removed"));
+        }
+
+        let completed = status.iter().map(|x| x.completed_length).sum();
+        let total = status.iter().map(|x| x.total_length).sum();
+        let speed = status.iter().map(|x| x.download_speed).sum();
+        if on_progress(completed, total, speed) == false {
+            return Err(anyhow::anyhow!("Download stopped"));
+        }
+
+        if status.iter().all(|x| x.status == "complete") {
+            return Ok(());
+        }
+    }
+}
+
+/// Netscape cookie files have tab separated lines, unlike a raw cookie string.
+fn is_cookie_file(cookies: &str) -> bool {
+    cookies.contains('\t') || cookies.lines().count() > 1
+}
+
+/// Returns output dir and file name without extension.
+pub(super) fn get_output(download_info: &DownloadInfo) -> (PathBuf, String) {
+    let (output_dir, output_name) = download_info
+        .save_option
+        .as_ref()
+        .map(|x| (x.output_dir.clone(), x.file_name.clone()))
+        .unwrap_or(("./".to_owned(), download_info.title.clone()));
+    (PathBuf::from(output_dir), output_name)
+}
+
+/// Files of direct links may have no extension.
+pub(super) fn with_ext(name: &str, ext: &str) -> String {
+    match ext.len() {
+        0 => name.to_owned(),
+        _ => format!("{}.{}", name, ext),
+    }
+}
+
+pub(super) fn get_part_headers(download_info: &DownloadInfo) -> Vec<String> {
+    let mut headers = download_info.headers.clone();
+    if headers.iter().all(|x| x.to_ascii_lowercase().starts_with("referer:") == false) {
+        headers.push(format!("Referer: {}", download_info.url));
+    }
+    if let Some(cookies) = download_info.cookies.as_ref() {
+        // Only a raw cookie string can be sent as header, not a cookie file
+        if is_cookie_file(cookies) == false && cookies.trim().len() > 0 {
+            headers.push(format!("Cookie: {}", cookies.trim()));
+        }
+    }
+    headers
+}
+
+/// Joins the parts with ffmpeg, muxing them as separate tracks if `need_mux`
+/// (e.g. video and audio) or concatenating them otherwise.
+pub(super) fn merge_parts(part_files: &[PathBuf], output: &Path, need_mux: bool) -> Result<()> {
+    if let [part_file] = part_files {
+        std::fs::rename(part_file, output)?;
+        return Ok(());
+    }
+
+    let mut command = create_hide_window_command("ffmpeg");
+    command.arg("-y");
+
+    // Named after the whole output file so a sibling "Talk.txt" is never overwritten
+    let mut list_file = output.as_os_str().to_owned();
+    list_file.push(".ugdown-parts.txt");
+    let list_file = PathBuf::from(list_file);
+    if need_mux {
+        for part_file in part_files {
+            command.arg("-i").arg(part_file);
+        }
+        for i in 0..part_files.len() {
+            command.arg("-map").arg(i.to_string());
+        }
+    } else {
+        let list: Vec<String> = part_files
+            .iter()
+            .map(|x| format!("file '{}'", x.to_string_lossy().replace('\'', "'\\''")))
+            .collect();
+        std::fs::write(&list_file, list.join("\n"))?;
+        command
+            .args(["-f", "concat", "-safe", "0", "-i"])
+            .arg(&list_file);
+    }
+
+    let status = command
+        .args(["-c", "copy"])
+        .arg(output)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|_| anyhow::anyhow!("ffmpeg is not found, parts are kept"))?;
+
+    let _ = std::fs::remove_file(&list_file);
+    if status.success() == false {
+        return Err(anyhow::anyhow!("ffmpeg failed to merge parts"));
+    }
+
+    for part_file in part_files {
+        let _ = std::fs::remove_file(part_file);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+
+use super::aria2::{get_output, get_part_headers, merge_parts, with_ext};
+use super::proxy::build_agent;
+use super::*;
+
+/// Downloads all parts of `download_info` over plain HTTP, one after another, then
+/// joins them into one file like [`download_parts`].
+///
+/// `on_progress` receives completed bytes, total bytes and speed, and returns false to stop.
+pub fn download_parts_direct(
+    download_info: &DownloadInfo,
+    mut on_progress: impl FnMut(u64, u64, u64) -> bool,
+) -> Result<PathBuf> {
+    let (output_dir, output_name) = get_output(download_info);
+    std::fs::create_dir_all(&output_dir)?;
+    let headers: Vec<(String, String)> = get_part_headers(download_info)
+        .iter()
+        .filter_map(|x| x.split_once(':'))
+        .map(|(name, value)| (name.trim().to_owned(), value.trim().to_owned()))
+        .collect();
+
+    let mut total: u64 = download_info.parts.iter().map(|x| x.size).sum();
+    let mut completed: u64 = 0;
+    let mut part_files = Vec::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut throttle = Throttle::new(download_info.speed_limit);
+    for (i, part) in download_info.parts.iter().enumerate() {
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(Duration::from_secs(10))
+            .timeout_read(Duration::from_secs(30));
+        let mut request = build_agent(agent, &part.url).build().get(&part.url);
+        for (name, value) in &headers {
+            request = request.set(name, value);
+        }
+        let response = request.call()?;
+        if part.size == 0 {
+            total = total
+                + response
+                    .header("Content-Length")
+                    .and_then(|x| x.parse::<u64>().ok())
+                    .unwrap_or(0);
+        }
+
+        let part_file = output_dir.join(with_ext(&format!("{}[{}]", output_name, i), &part.ext));
+        let mut file = std::fs::File::create(&part_file)?;
+        let mut reader = response.into_reader();
+        let mut before = Instant::now();
+        let mut received = 0;
+        loop {
+            let chunk = throttle.get_chunk_size(buf.len());
+            let length = reader.read(&mut buf[..chunk])?;
+            if length == 0 {
+                break;
+            }
+            throttle.consume(length as u64);
+            file.write_all(&buf[..length])?;
+            completed = completed + length as u64;
+            received = received + length;
+
+            let dur = before.elapsed();
+            if dur >= Duration::from_millis(500) {
+                let speed = (received as f64 / dur.as_secs_f64()) as u64;
+                before = Instant::now();
+                received = 0;
+                if on_progress(completed, total.max(completed), speed) == false {
+                    return Err(anyhow!("Download stopped"));
+                }
+            }
+        }
+        part_files.push(part_file);
+    }
+    on_progress(completed, total.max(completed), 0);
+
+    let output = output_dir.join(with_ext(&output_name, &download_info.ext));
+    merge_parts(&part_files, &output, download_info.need_mux)?;
+    Ok(output)
+}
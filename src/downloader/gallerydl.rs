@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde_json::Value;
+
+use super::*;
+
+// See https://github.com/mikf/gallery-dl/blob/master/gallery_dl/extractor/message.py
+const MESSAGE_DIRECTORY: u64 = 2;
+const MESSAGE_URL: u64 = 3;
+const MESSAGE_QUEUE: u64 = 6;
+
+#[derive(Debug)]
+struct GalleryNode {
+    url: String,
+    title: String,
+    site: String,
+    ext: String,
+    first_index: usize,
+    file_count: usize,
+    is_queue: bool,
+}
+
+impl GalleryNode {
+    fn new(url: &str, kwdict: &Value, first_index: usize) -> Self {
+        let title = ["title", "gallery_id", "id"]
+            .iter()
+            .find_map(|key| match kwdict.get(key) {
+                Some(Value::String(value)) => Some(value.clone()),
+                Some(Value::Number(value)) => Some(value.to_string()),
+                _ => None,
+            })
+            .unwrap_or(format!(
+                "{} {}",
+                kwdict["category"].as_str().unwrap_or("Unknown"),
+                kwdict["subcategory"].as_str().unwrap_or("")
+            ));
+
+        Self {
+            url: url.to_owned(),
+            title: title.trim().to_owned(),
+            site: kwdict["category"].as_str().unwrap_or("Unknown").to_owned(),
+            ext: "Unknown".to_owned(),
+            first_index,
+            file_count: 0,
+            is_queue: false,
+        }
+    }
+}
+
+/// Parses `gallery-dl -j` output, one stream for each gallery.
+pub fn parse_stream_info(
+    url: &str,
+    downloader: &str,
+    output: &str,
+) -> Result<HashMap<String, DownloadInfo>> {
+    let messages: Vec<Value> = serde_json::from_str(output)?;
+
+    let mut galleries: Vec<GalleryNode> = Vec::new();
+    let mut file_index = 0;
+
+    for message in &messages {
+        match message[0].as_u64() {
+            Some(MESSAGE_DIRECTORY) => {
+                galleries.push(GalleryNode::new(url, &message[1], file_index + 1));
+            }
+            Some(MESSAGE_URL) => {
+                file_index = file_index + 1;
+                if galleries.last().map(|x| x.is_queue).unwrap_or(true) {
+                    galleries.push(GalleryNode::new(url, &message[2], file_index));
+                }
+                if let Some(gallery) = galleries.last_mut() {
+                    gallery.file_count = gallery.file_count + 1;
+                    if let Some(ext) = message[2]["extension"].as_str() {
+                        gallery.ext = ext.to_owned();
+                    }
+                }
+            }
+            Some(MESSAGE_QUEUE) => {
+                if let Some(queue_url) = message[1].as_str() {
+                    let mut gallery = GalleryNode::new(queue_url, &message[2], 0);
+                    gallery.is_queue = true;
+                    galleries.push(gallery);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let galleries: Vec<GalleryNode> = galleries
+        .into_iter()
+        .filter(|x| x.is_queue || x.file_count > 0)
+        .collect();
+    let is_single = galleries.len() == 1;
+
+    let mut info_map = HashMap::new();
+
+    for (i, gallery) in galleries.iter().enumerate() {
+        // Pick files by index range, unless the whole url is a single gallery
+        let stream_id = match gallery.is_queue || is_single {
+            true => "".to_owned(),
+            false => format!(
+                "{}-{}",
+                gallery.first_index,
+                gallery.first_index + gallery.file_count - 1
+            ),
+        };
+
+        let stream_name = match gallery.is_queue {
+            true => "Gallery".to_owned(),
+            false => format!("{} files", gallery.file_count),
+        };
+
+        let info = DownloadInfo {
+            url: gallery.url.clone(),
+            site: gallery.site.clone(),
+            title: gallery.title.clone(),
+            ext: gallery.ext.clone(),
+            stream_id,
+            stream_name,
+            downloader: downloader.to_owned(),
+            file_count: gallery.file_count,
+            ..Default::default()
+        };
+
+        info_map.insert(i.to_string(), info);
+    }
+
+    Ok(info_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stream_info() {
+        let output = r#"[
+            [2, {"category": "imgur", "subcategory": "album", "title": "Sketches"}],
+            [3, "https://i.imgur.com/a.png", {"category": "imgur", "extension": "png"}],
+            [3, "https://i.imgur.com/b.png", {"category": "imgur", "extension": "png"}],
+            [2, {"category": "imgur", "subcategory": "album", "title": "Colors"}],
+            [3, "https://i.imgur.com/c.jpg", {"category": "imgur", "extension": "jpg"}]
+        ]"#;
+
+        let info_map = parse_stream_info("https://imgur.com/a/x", "gallery-dl", output).unwrap();
+        assert_eq!(2, info_map.len());
+        assert_eq!("Sketches", info_map["0"].title);
+        assert_eq!("1-2", info_map["0"].stream_id);
+        assert_eq!(2, info_map["0"].file_count);
+        assert_eq!("3-3", info_map["1"].stream_id);
+        assert_eq!("jpg", info_map["1"].ext);
+    }
+}
@@ -0,0 +1,85 @@
+use std::path::Path;
+
+/// Extracts http(s) urls from text, e.g. a plain list, csv, `.url` or `.webloc` file.
+pub fn extract_urls(text: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for found in URL_REGEX.find_iter(text) {
+        let url = found
+            .as_str()
+            .trim_end_matches(|c| ".;)]".contains(c))
+            .to_owned();
+        if urls.contains(&url) == false {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+/// Extracts urls from dropped or pasted text, where a line may be a path of a list file.
+pub fn import_urls(text: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for line in text.lines().map(|x| x.trim()).filter(|x| x.len() > 0) {
+        let found = match get_dropped_file(line) {
+            Some(path) => std::fs::read_to_string(path)
+                .map(|x| extract_urls(&x))
+                .unwrap_or_default(),
+            None => extract_urls(line),
+        };
+        for url in found {
+            if urls.contains(&url) == false {
+                urls.push(url);
+            }
+        }
+    }
+    urls
+}
+
+/// Dropped files come as plain paths on Windows and `file://` urls elsewhere.
+fn get_dropped_file(line: &str) -> Option<std::path::PathBuf> {
+    let path = match line.starts_with("file://") {
+        true => url::Url::parse(line).ok()?.to_file_path().ok()?,
+        false => Path::new(line).to_path_buf(),
+    };
+    match path.is_file() {
+        true => Some(path),
+        false => None,
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref URL_REGEX: regex::Regex = regex::Regex::new(r#"https?://[^\s,"'<>]+"#).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_urls() {
+        let text = "title,url\nfoo,https://youtu.be/a\nbar,\"https://b23.tv/b\"\n";
+        assert_eq!(
+            vec!["https://youtu.be/a", "https://b23.tv/b"],
+            extract_urls(text)
+        );
+
+        // Url scheme handlers get the url behind `ugdown://`
+        assert_eq!(
+            vec!["https://youtu.be/a"],
+            extract_urls("ugdown://https://youtu.be/a")
+        );
+
+        let url_file = "[InternetShortcut]\r\nURL=https://www.bilibili.com/video/BV1\r\n";
+        assert_eq!(
+            vec!["https://www.bilibili.com/video/BV1"],
+            extract_urls(url_file)
+        );
+
+        let webloc = "<dict><key>URL</key><string>https://vimeo.com/1</string></dict>";
+        assert_eq!(vec!["https://vimeo.com/1"], extract_urls(webloc));
+
+        assert_eq!(
+            vec!["https://a.com/x"],
+            extract_urls("see https://a.com/x. and https://a.com/x")
+        );
+    }
+}
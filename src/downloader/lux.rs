@@ -1,8 +1,4 @@
-use std::{
-    collections::HashMap,
-    path::Path,
-    process::{Child, Stdio},
-};
+use std::collections::HashMap;
 
 use anyhow::Result;
 use serde::Deserialize;
@@ -49,120 +45,51 @@ struct LuxInfo {
     ext: String,
 }
 
-pub struct Lux {}
-
-impl Downloader for Lux {
-    fn get_downloader_name(&self) -> String {
-        "Lux".to_owned()
-    }
-
-    fn get_stream_info(
-        &self,
-        url: &str,
-        cookie_file: Option<&Path>,
-    ) -> Result<HashMap<String, DownloadInfo>> {
-        let result = match &cookie_file {
-            Some(file) => create_hide_window_command("lux")
-                .arg("-c")
-                .arg(file)
-                .arg("-j")
-                .arg(url)
-                .output()?,
-            None => create_hide_window_command("lux")
-                .arg("-j")
-                .arg(url)
-                .output()?,
-        };
-
-        let result = String::from_utf8(result.stdout.to_vec())?;
-        let result: Vec<LuxNode> = serde_json::from_str(&result)?;
-
-        let node = result
-            .get(0)
-            .ok_or_else(|| anyhow::anyhow!("Wrong at get 0 index ??"))?;
-
-        let mut info_map = HashMap::new();
-
-        let site = &node.site;
-        let title = &node.title;
-
-        for (stream_id, stream_node) in &node.streams {
-            let info = DownloadInfo {
-                url: url.to_string(),
-                site: site.clone(),
-                title: title.clone(),
-                ext: stream_node
-                    .parts
-                    .first()
-                    .and_then(|x| Some(x.ext.to_owned()))
-                    .unwrap_or(stream_node.ext.clone()),
-                stream_id: stream_id.clone(),
-                stream_name: stream_node.quality.clone(),
-                stream_size: stream_node.size,
-                downloader: self.get_downloader_name(),
-                ..Default::default()
-            };
-
-            info_map.insert(stream_id.clone(), info);
-        }
-
-        Ok(info_map)
-    }
-
-    fn execute_download(
-        &self,
-        url: &str,
-        id: &str,
-        output_path: &str,
-        output_name: &str,
-        cookie_file: Option<&Path>,
-    ) -> anyhow::Result<Child> {
-        let child = match &cookie_file {
-            Some(cookie_file) => create_hide_window_command("lux")
-                .arg("-c")
-                .arg(cookie_file)
-                .arg("-f")
-                .arg(id)
-                .arg("-o")
-                .arg(output_path)
-                .arg("-O")
-                .arg(output_name)
-                .arg(url)
-                .stdin(Stdio::null())
-                .stdout(Stdio::null())
-                .stderr(Stdio::piped())
-                .spawn()?,
-            None => create_hide_window_command("lux")
-                .arg("-f")
-                .arg(id)
-                .arg("-o")
-                .arg(output_path)
-                .arg("-O")
-                .arg(output_name)
-                .arg(url)
-                .stdin(Stdio::null())
-                .stdout(Stdio::null())
-                .stderr(Stdio::piped())
-                .spawn()?,
+pub fn parse_stream_info(
+    url: &str,
+    downloader: &str,
+    output: &str,
+) -> Result<HashMap<String, DownloadInfo>> {
+    let result: Vec<LuxNode> = serde_json::from_str(output)?;
+
+    let node = result
+        .get(0)
+        .ok_or_else(|| anyhow::anyhow!("Wrong at get 0 index ??"))?;
+
+    let mut info_map = HashMap::new();
+
+    let site = &node.site;
+    let title = &node.title;
+
+    for (stream_id, stream_node) in &node.streams {
+        let info = DownloadInfo {
+            url: url.to_string(),
+            site: site.clone(),
+            title: title.clone(),
+            ext: stream_node
+                .parts
+                .first()
+                .and_then(|x| Some(x.ext.to_owned()))
+                .unwrap_or(stream_node.ext.clone()),
+            stream_id: stream_id.clone(),
+            stream_name: stream_node.quality.clone(),
+            stream_size: stream_node.size,
+            downloader: downloader.to_owned(),
+            parts: stream_node
+                .parts
+                .iter()
+                .map(|x| PartInfo {
+                    url: x.url.clone(),
+                    size: x.size,
+                    ext: x.ext.clone(),
+                })
+                .collect(),
+            need_mux: stream_node.need_mux,
+            ..Default::default()
         };
 
-        Ok(child)
-    }
-
-    fn is_stderr_output(&self) -> bool {
-        true
+        info_map.insert(stream_id.clone(), info);
     }
 
-    fn get_program(&self) -> Result<(PathBuf, String)> {
-        let mut command = create_hide_window_command("lux");
-        let program = which::which(command.get_program())?;
-        let result = command.arg("--version").output()?;
-        let result = String::from_utf8(result.stdout.to_vec())?;
-        let re = regex::Regex::new(r"([0-9]+\.[0-9]+\.[0-9]+)").unwrap();
-        let version = re
-            .find(&result)
-            .map(|x| x.as_str().to_string())
-            .unwrap_or("Unknown".to_owned());
-        Ok((program, version))
-    }
+    Ok(info_map)
 }
@@ -0,0 +1,389 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    process::{Child, Stdio},
+    sync::RwLock,
+};
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use super::proxy::{get_proxy, get_proxy_envs};
+use super::*;
+use crate::config::get_app_dir;
+
+pub(super) const BUILTIN_MANIFESTS: [&str; 6] = [
+    include_str!("engines/lux.toml"),
+    include_str!("engines/you-get.toml"),
+    include_str!("engines/youtube-dl.toml"),
+    include_str!("engines/gallery-dl.toml"),
+    include_str!("engines/streamlink.toml"),
+    include_str!("engines/yt-dlp.toml"),
+];
+
+lazy_static::lazy_static! {
+    /// Manifests along with errors of user manifests failing to parse, loaded on first use.
+    static ref MANIFESTS: RwLock<Option<(Vec<EngineManifest>, Vec<String>)>> = RwLock::new(None);
+}
+
+/// Declarative engine definition, loaded from `~/.ugdown/engines/*.toml`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct EngineManifest {
+    pub name: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub program: String,
+    pub parser: ParserType,
+    #[serde(default)]
+    pub progress: ProgressOutput,
+    #[serde(default)]
+    pub progress_format: ProgressFormat,
+    #[serde(default = "default_version_regex")]
+    pub version_regex: String,
+    pub pip_package: Option<String>,
+    /// Hosts of supported sites, in the same form as domain rule patterns.
+    #[serde(default)]
+    pub sites: Vec<String>,
+    pub args: EngineArgs,
+    pub github: Option<GithubSource>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EngineArgs {
+    pub info: Vec<String>,
+    pub download: Vec<String>,
+    #[serde(default)]
+    pub cookies: Vec<String>,
+    #[serde(default)]
+    pub format: Vec<String>,
+    #[serde(default)]
+    pub version: Vec<String>,
+    /// Lists a channel or playlist as JSON, without detecting each video.
+    #[serde(default)]
+    pub playlist: Vec<String>,
+    /// Limits download speed, `{rate}` is bytes per second.
+    #[serde(default)]
+    pub rate_limit: Vec<String>,
+    /// Connects through proxy `{proxy}`, engines without it read `HTTP_PROXY` and alike.
+    #[serde(default)]
+    pub proxy: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GithubSource {
+    pub owner: String,
+    pub repo: String,
+    #[serde(default)]
+    pub tag_prefix: String,
+    /// Asset url patterns keyed by `<os>_<arch>`, `{tag}` and `{version}` are replaced.
+    #[serde(default)]
+    pub assets: HashMap<String, String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ParserType {
+    Lux,
+    YoutubeDl,
+    YouGet,
+    GalleryDl,
+    Streamlink,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProgressOutput {
+    #[default]
+    Stdout,
+    Stderr,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProgressFormat {
+    /// Progress bars with percent, e.g. `45.2%`.
+    #[default]
+    Percent,
+    /// One line for each downloaded file.
+    FileLines,
+    /// Bytes written so far, e.g. `Written 12.3 MiB`.
+    WrittenBytes,
+}
+
+fn default_version_regex() -> String {
+    r"([0-9]+\.[0-9]+\.[0-9]+)".to_owned()
+}
+
+impl EngineManifest {
+    pub fn is_match(&self, engine: &str) -> bool {
+        let engine = engine.to_ascii_lowercase();
+        let engine = engine.trim();
+        self.name.to_ascii_lowercase() == engine
+            || self.aliases.iter().any(|x| x.to_ascii_lowercase() == engine)
+    }
+}
+
+/// Returns the built-in manifests followed by user ones, user manifests with the
+/// same name replace the built-in ones.
+pub fn load_manifests() -> Vec<EngineManifest> {
+    if let Some((manifests, _)) = MANIFESTS.read().unwrap().as_ref() {
+        return manifests.clone();
+    }
+    reload_manifests();
+    MANIFESTS.read().unwrap().as_ref().unwrap().0.clone()
+}
+
+/// Reads the manifests again after user ones changed, returning their parse errors.
+pub fn reload_manifests() -> Vec<String> {
+    let mut manifests: Vec<EngineManifest> = BUILTIN_MANIFESTS
+        .iter()
+        .filter_map(|x| toml::from_str(x).ok())
+        .collect();
+
+    let (user_manifests, errors) = load_user_manifests();
+    for manifest in user_manifests {
+        match manifests.iter().position(|x| x.name == manifest.name) {
+            Some(index) => manifests[index] = manifest,
+            None => manifests.push(manifest),
+        }
+    }
+
+    *MANIFESTS.write().unwrap() = Some((manifests, errors.clone()));
+    errors
+}
+
+/// Gets parse errors of user manifests, one `<file>: <error>` each.
+pub fn get_manifest_errors() -> Vec<String> {
+    load_manifests();
+    MANIFESTS.read().unwrap().as_ref().unwrap().1.clone()
+}
+
+fn load_user_manifests() -> (Vec<EngineManifest>, Vec<String>) {
+    let engine_dir = match get_app_dir() {
+        Ok(app_dir) => app_dir.join("engines"),
+        Err(_) => return (Vec::new(), Vec::new()),
+    };
+
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(engine_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|x| x.ok())
+                .map(|x| x.path())
+                .filter(|x| x.extension().map(|ext| ext == "toml").unwrap_or(false))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+
+    let mut manifests = Vec::new();
+    let mut errors = Vec::new();
+    for path in paths {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        match std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|x| Ok(toml::from_str(&x)?))
+        {
+            Ok(manifest) => manifests.push(manifest),
+            Err(error) => errors.push(format!("{}: {}", file_name, error)),
+        }
+    }
+    (manifests, errors)
+}
+
+pub fn get_manifest(engine: &str) -> Option<EngineManifest> {
+    load_manifests().into_iter().find(|x| x.is_match(engine))
+}
+
+fn expand_args(args: &[String], values: &[(&str, &str)]) -> Vec<String> {
+    args.iter()
+        .map(|arg| {
+            values.iter().fold(arg.to_owned(), |arg, (key, value)| {
+                arg.replace(&format!("{{{}}}", key), value)
+            })
+        })
+        .collect()
+}
+
+pub struct ManifestEngine {
+    pub manifest: EngineManifest,
+}
+
+impl ManifestEngine {
+    fn cookie_args(&self, cookie_file: Option<&Path>) -> Vec<String> {
+        match cookie_file {
+            Some(cookie_file) => expand_args(
+                &self.manifest.args.cookies,
+                &[("cookie_file", &cookie_file.to_string_lossy())],
+            ),
+            None => Vec::new(),
+        }
+    }
+
+    fn proxy_args(&self, url: &str) -> Vec<String> {
+        match get_proxy(url) {
+            Some(proxy) => expand_args(&self.manifest.args.proxy, &[("proxy", &proxy)]),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl Downloader for ManifestEngine {
+    fn get_downloader_name(&self) -> String {
+        self.manifest.name.clone()
+    }
+
+    fn get_stream_info(
+        &self,
+        url: &str,
+        cookie_file: Option<&Path>,
+    ) -> Result<HashMap<String, DownloadInfo>> {
+        let result = create_hide_window_command(&self.manifest.program)
+            .args(self.cookie_args(cookie_file))
+            .args(self.proxy_args(url))
+            .args(expand_args(&self.manifest.args.info, &[("url", url)]))
+            .envs(get_proxy_envs(url))
+            .output()?;
+
+        let result = String::from_utf8(result.stdout.to_vec())?;
+        let downloader = self.get_downloader_name();
+
+        match self.manifest.parser {
+            ParserType::Lux => lux::parse_stream_info(url, &downloader, &result),
+            ParserType::YoutubeDl => youtubedl::parse_stream_info(url, &downloader, &result),
+            ParserType::YouGet => youget::parse_stream_info(url, &downloader, &result),
+            ParserType::GalleryDl => gallerydl::parse_stream_info(url, &downloader, &result),
+            ParserType::Streamlink => streamlink::parse_stream_info(url, &downloader, &result),
+        }
+    }
+
+    fn execute_download(
+        &self,
+        url: &str,
+        id: &str,
+        output_dir: &str,
+        output_name: &str,
+        cookie_file: Option<&Path>,
+        rate_limit: Option<u64>,
+    ) -> Result<Child> {
+        let values = [
+            ("url", url),
+            ("id", id),
+            ("output_dir", output_dir),
+            ("output_name", output_name),
+        ];
+
+        let (stdout, stderr) = match self.manifest.progress {
+            ProgressOutput::Stdout => (Stdio::piped(), Stdio::null()),
+            ProgressOutput::Stderr => (Stdio::null(), Stdio::piped()),
+        };
+
+        // An empty stream id downloads everything at the url
+        let format_args = match id.len() {
+            0 => Vec::new(),
+            _ => expand_args(&self.manifest.args.format, &values),
+        };
+        let rate_args = match rate_limit {
+            Some(rate) => {
+                expand_args(&self.manifest.args.rate_limit, &[("rate", &rate.to_string())])
+            }
+            None => Vec::new(),
+        };
+
+        let child = create_hide_window_command(&self.manifest.program)
+            .args(self.cookie_args(cookie_file))
+            .args(self.proxy_args(url))
+            .args(format_args)
+            .args(rate_args)
+            .args(expand_args(&self.manifest.args.download, &values))
+            .envs(get_proxy_envs(url))
+            .stdin(Stdio::null())
+            .stdout(stdout)
+            .stderr(stderr)
+            .spawn()?;
+
+        Ok(child)
+    }
+
+    fn is_stderr_output(&self) -> bool {
+        self.manifest.progress == ProgressOutput::Stderr
+    }
+
+    fn list_entries(&self, url: &str, cookie_file: Option<&Path>) -> Result<Vec<PlaylistEntry>> {
+        if self.manifest.args.playlist.len() == 0 || self.manifest.parser != ParserType::YoutubeDl {
+            return Err(anyhow::anyhow!("{} can't list playlists", self.manifest.name));
+        }
+
+        let result = create_hide_window_command(&self.manifest.program)
+            .args(self.cookie_args(cookie_file))
+            .args(self.proxy_args(url))
+            .args(expand_args(&self.manifest.args.playlist, &[("url", url)]))
+            .envs(get_proxy_envs(url))
+            .stdin(Stdio::null())
+            .output()?;
+        if result.status.success() == false {
+            let error = String::from_utf8_lossy(&result.stderr);
+            return Err(anyhow::anyhow!(
+                "{}",
+                error.lines().last().unwrap_or("Failed to list playlist")
+            ));
+        }
+
+        youtubedl::parse_playlist(&String::from_utf8(result.stdout)?)
+    }
+
+    fn parse_progress(&self, line: &str) -> Option<Progress> {
+        match self.manifest.progress_format {
+            ProgressFormat::Percent => parse_percent_progress(line),
+            ProgressFormat::FileLines if line.trim().len() > 0 => Some(Progress::File),
+            ProgressFormat::FileLines => None,
+            ProgressFormat::WrittenBytes => streamlink::parse_written_progress(line),
+        }
+    }
+
+    fn get_program(&self) -> Result<(PathBuf, String)> {
+        let mut command = create_hide_window_command(&self.manifest.program);
+        let program = which::which(command.get_program())?;
+        let result = command
+            .args(&self.manifest.args.version)
+            .stdin(Stdio::null())
+            .output()?;
+
+        // Some tools, e.g. you-get, print their version to stderr
+        let result = format!(
+            "{}\n{}",
+            String::from_utf8_lossy(&result.stdout),
+            String::from_utf8_lossy(&result.stderr)
+        );
+        let re = regex::Regex::new(&self.manifest.version_regex)?;
+        let version = re
+            .find(&result)
+            .map(|x| x.as_str().to_string())
+            .unwrap_or("Unknown".to_owned());
+        Ok((program, version))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_manifests() {
+        let manifests: Vec<EngineManifest> = BUILTIN_MANIFESTS
+            .iter()
+            .map(|x| toml::from_str(x).unwrap())
+            .collect();
+        assert_eq!(6, manifests.len());
+        assert!(manifests[1].is_match("YouGet"));
+        assert_eq!(ParserType::YoutubeDl, manifests[2].parser);
+    }
+
+    #[test]
+    fn test_expand_args() {
+        let args = vec!["-o".to_owned(), "{output_dir}/{output_name}".to_owned()];
+        assert_eq!(
+            vec!["-o", "downloads/video.mp4"],
+            expand_args(&args, &[("output_dir", "downloads"), ("output_name", "video.mp4")])
+        );
+    }
+}
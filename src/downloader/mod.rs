@@ -7,10 +7,44 @@ use std::{
 
 use anyhow::Result;
 
+use crate::config::{get_app_dir, get_config};
+
+mod aria2;
+mod direct;
+mod gallerydl;
+mod import;
 mod lux;
+mod manifest;
+mod preference;
+mod protocol;
+mod proxy;
+mod rules;
+mod schedule;
+mod streamlink;
+mod throttle;
+mod venv;
+mod version;
 mod youget;
 mod youtubedl;
 
+pub use aria2::download_parts;
+pub use direct::download_parts_direct;
+pub use import::{extract_urls, import_urls};
+pub use manifest::{
+    get_manifest, get_manifest_errors, load_manifests, reload_manifests, EngineManifest,
+    GithubSource,
+};
+pub use preference::Preference;
+pub use proxy::{http_get, parse_proxy_url};
+pub use rules::{expand_file_name, get_rule, is_supported_url, select_tasks};
+pub use schedule::{TaskSchedule, TimeWindow};
+pub use throttle::{
+    get_global_limit, get_process_limit, get_running_count, set_global_limit, set_running_count,
+    Throttle,
+};
+pub use venv::{install_venv_package, uninstall_venv};
+pub use version::*;
+
 #[derive(Clone, Debug, Default)]
 pub struct DownloadInfo {
     pub url: String,
@@ -23,6 +57,105 @@ pub struct DownloadInfo {
     pub downloader: String,
     pub save_option: Option<SaveOption>,
     pub cookies: Option<String>,
+    /// Number of files in a gallery, 0 for single file streams.
+    pub file_count: usize,
+    /// Live streams have no known size and are recorded until stopped.
+    pub is_live: bool,
+    pub record_limit: Option<RecordLimit>,
+    pub schedule: Option<TaskSchedule>,
+    /// Bytes per second overriding the global cap, `Some(0)` for unlimited.
+    pub speed_limit: Option<u64>,
+    /// Direct urls of the stream parts, if the engine exposes them.
+    pub parts: Vec<PartInfo>,
+    /// Parts are separate tracks (e.g. video and audio) to be muxed rather than concatenated.
+    pub need_mux: bool,
+    /// Extra request headers of the parts, e.g. `Referer: https://example.com/`.
+    pub headers: Vec<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fps: Option<f64>,
+    pub vcodec: Option<String>,
+    pub acodec: Option<String>,
+    /// Total bitrate in kbps.
+    pub bitrate: Option<u32>,
+    pub has_audio: Option<bool>,
+    pub has_video: Option<bool>,
+    /// Duration in seconds.
+    pub duration: Option<f64>,
+    pub language: Option<String>,
+    /// Identity in youtube-dl archive format `<extractor> <id>`, if the engine reports it.
+    pub archive_id: Option<String>,
+}
+
+impl DownloadInfo {
+    pub fn is_video_only(&self) -> bool {
+        self.has_video == Some(true) && self.has_audio == Some(false)
+    }
+
+    pub fn is_audio_only(&self) -> bool {
+        self.has_video == Some(false)
+    }
+
+    /// Streams with both tracks, or unknown ones, which engines report without track info.
+    pub fn is_muxed(&self) -> bool {
+        !self.is_video_only() && !self.is_audio_only()
+    }
+
+    /// Output file path as planned, which engines may still change.
+    pub fn get_output(&self) -> String {
+        self.save_option
+            .as_ref()
+            .map(|x| {
+                let file_name = match self.ext.len() {
+                    0 => x.file_name.clone(),
+                    _ => format!("{}.{}", x.file_name, self.ext),
+                };
+                PathBuf::from(&x.output_dir).join(file_name).to_string_lossy().to_string()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PartInfo {
+    pub url: String,
+    pub size: u64,
+    pub ext: String,
+}
+
+/// Stops a recording after a duration or at a local wall-clock time, whichever comes first.
+#[derive(Clone, Debug, Default)]
+pub struct RecordLimit {
+    pub stop_after: Option<std::time::Duration>,
+    pub stop_at: Option<chrono::NaiveTime>,
+}
+
+impl RecordLimit {
+    /// Returns how long the recording may run when started now.
+    pub fn get_remaining(&self) -> Option<std::time::Duration> {
+        let stop_at = self.stop_at.and_then(|stop_at| {
+            let now = chrono::Local::now().naive_local();
+            let mut deadline = now.date().and_time(stop_at);
+            if deadline <= now {
+                deadline = deadline + chrono::Duration::days(1);
+            }
+            (deadline - now).to_std().ok()
+        });
+
+        match (self.stop_after, stop_at) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// A video of a channel or playlist, listed without detecting its streams.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlaylistEntry {
+    pub url: String,
+    pub title: String,
+    /// Identity in youtube-dl archive format, see `DownloadInfo::archive_id`.
+    pub archive_id: Option<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -31,6 +164,24 @@ pub struct SaveOption {
     pub file_name: String,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub enum Progress {
+    /// Progress of a single file, between 0 and 1.
+    Percent(f64),
+    /// One more file of a gallery is done.
+    File,
+    /// Total bytes written of a live recording.
+    Bytes(u64),
+    /// Exact bytes and speed from a transfer backend.
+    Transfer {
+        completed: u64,
+        total: u64,
+        speed: u64,
+    },
+    /// The engine reports the download failed, with its message.
+    Failed(String),
+}
+
 pub trait Downloader {
     fn get_downloader_name(&self) -> String;
     fn get_stream_info(
@@ -45,11 +196,33 @@ pub trait Downloader {
         output_dir: &str,
         output_name: &str,
         cookie_file: Option<&Path>,
+        rate_limit: Option<u64>,
     ) -> Result<Child>;
     fn is_stderr_output(&self) -> bool;
+    /// Lists videos of a channel or playlist url.
+    fn list_entries(&self, _url: &str, _cookie_file: Option<&Path>) -> Result<Vec<PlaylistEntry>> {
+        Err(anyhow::anyhow!(
+            "{} can't list playlists",
+            self.get_downloader_name()
+        ))
+    }
+    /// Parses a line of download output into progress.
+    fn parse_progress(&self, line: &str) -> Option<Progress> {
+        parse_percent_progress(line)
+    }
     fn get_program(&self) -> Result<(PathBuf, String)>;
 }
 
+pub fn parse_percent_progress(line: &str) -> Option<Progress> {
+    let caps = PROGRESS_REGEX.captures(line)?;
+    let progress = caps.name("progress")?.as_str().parse::<f64>().ok()?;
+    Some(Progress::Percent(progress / 100.0))
+}
+
+lazy_static::lazy_static! {
+    static ref PROGRESS_REGEX: regex::Regex = regex::Regex::new(r"(?<progress>[0-9\.]*?)%").unwrap();
+}
+
 pub fn store_cookies(cookies: &str) -> Result<PathBuf> {
     let cookie_id = uuid::Uuid::new_v4();
     let cookie_file = std::env::temp_dir().join(format!("cookie_{}.txt", cookie_id.to_string()));
@@ -57,22 +230,37 @@ pub fn store_cookies(cookies: &str) -> Result<PathBuf> {
     Ok(cookie_file)
 }
 
-use lux::Lux;
-use youget::Youget;
-use youtubedl::Youtubedl;
+use manifest::ManifestEngine;
+use protocol::get_protocol_engines;
+
+/// Pseudo engine which tries the fallback engines in order.
+pub const AUTO_ENGINE: &str = "Auto";
+
+/// Pseudo engine of plain file links, whose parts are downloaded without any engine.
+pub const DIRECT_ENGINE: &str = "direct";
 
 pub fn get_engine_names() -> Vec<String> {
-    ["lux", "you-get", "youtube-dl"]
-        .map(|x| x.to_string())
-        .to_vec()
+    let mut names: Vec<String> = load_manifests().into_iter().map(|x| x.name).collect();
+    for engine in get_protocol_engines() {
+        if names.contains(&engine.name) == false {
+            names.push(engine.name);
+        }
+    }
+    names
 }
 
 pub fn get_engine(engine: &str) -> Result<Box<dyn Downloader>> {
-    match engine.to_ascii_lowercase().trim() {
-        "lux" => Ok(Box::new(Lux {})),
-        "you-get" | "youget" => Ok(Box::new(Youget {})),
-        "youtube-dl" | "youtubedl" => Ok(Box::new(Youtubedl {})),
-        _ => Err(anyhow::anyhow!("engine are not supported {}", engine)),
+    if let Some(manifest) = get_manifest(engine) {
+        return Ok(Box::new(ManifestEngine { manifest }));
+    }
+
+    let name = engine.trim().to_ascii_lowercase();
+    match get_protocol_engines()
+        .into_iter()
+        .find(|x| x.name.to_ascii_lowercase() == name)
+    {
+        Some(protocol_engine) => Ok(Box::new(protocol_engine)),
+        None => Err(anyhow::anyhow!("engine are not supported {}", engine)),
     }
 }
 
@@ -81,13 +269,76 @@ pub fn get_stream_info(
     url: &str,
     cookie_file: Option<&Path>,
 ) -> Result<HashMap<String, DownloadInfo>> {
+    if engine == AUTO_ENGINE {
+        return get_stream_info_auto(url, cookie_file);
+    }
+
     let engine = get_engine(engine)?;
-    engine.get_stream_info(url, cookie_file)
+    let mut info_map = engine.get_stream_info(url, cookie_file)?;
+    for info in info_map.values_mut() {
+        preference::normalize_quality(info);
+    }
+    Ok(info_map)
+}
+
+/// Tries the engines in `engine_fallback` of config in order, until one returns streams.
+fn get_stream_info_auto(
+    url: &str,
+    cookie_file: Option<&Path>,
+) -> Result<HashMap<String, DownloadInfo>> {
+    let mut errors = Vec::new();
+
+    for engine in get_config()
+        .engine_fallback
+        .into_iter()
+        .filter(|x| x != AUTO_ENGINE)
+    {
+        match get_stream_info(&engine, url, cookie_file) {
+            Ok(info_map) if info_map.len() > 0 => {
+                // Keep ids unique if results of several engines are merged later
+                return Ok(info_map
+                    .into_iter()
+                    .map(|(id, info)| (format!("{}:{}", engine, id), info))
+                    .collect());
+            }
+            Ok(_) => errors.push(format!("{}: no stream", engine)),
+            Err(error) => errors.push(format!("{}: {}", engine, error)),
+        }
+    }
+
+    match errors.len() {
+        0 => Err(anyhow::anyhow!("No engine to try, please set fallback engines")),
+        _ => Err(anyhow::anyhow!("All engines failed, {}", errors.join("; "))),
+    }
+}
+
+/// Lists videos of a channel or playlist, Auto tries the fallback engines in order.
+pub fn list_entries(
+    engine: &str,
+    url: &str,
+    cookie_file: Option<&Path>,
+) -> Result<Vec<PlaylistEntry>> {
+    if engine != AUTO_ENGINE {
+        return get_engine(engine)?.list_entries(url, cookie_file);
+    }
+
+    let mut errors = Vec::new();
+    for engine in get_config()
+        .engine_fallback
+        .into_iter()
+        .filter(|x| x != AUTO_ENGINE)
+    {
+        match get_engine(&engine).and_then(|x| x.list_entries(url, cookie_file)) {
+            Ok(entries) => return Ok(entries),
+            Err(error) => errors.push(format!("{}: {}", engine, error)),
+        }
+    }
+    Err(anyhow::anyhow!("All engines failed, {}", errors.join("; ")))
 }
 
 pub fn execute_download_info(
     download_info: &DownloadInfo,
-) -> Result<(Child, Option<PathBuf>, bool)> {
+) -> Result<(Child, Option<PathBuf>, Box<dyn Downloader>)> {
     let download_info = download_info.clone();
     let (output_dir, output_name) = download_info
         .save_option
@@ -105,10 +356,18 @@ pub fn execute_download_info(
     let engine = get_engine(&download_info.downloader)?;
     let url = download_info.url;
     let id = download_info.stream_id;
+    let rate_limit = get_process_limit(download_info.speed_limit);
     Ok((
-        engine.execute_download(&url, &id, &output_dir, &output_name, cookie_file.as_deref())?,
+        engine.execute_download(
+            &url,
+            &id,
+            &output_dir,
+            &output_name,
+            cookie_file.as_deref(),
+            rate_limit,
+        )?,
         cookie_file,
-        engine.is_stderr_output(),
+        engine,
     ))
 }
 
@@ -128,8 +387,22 @@ pub fn create_hide_window_command<S: AsRef<OsStr>>(program: S) -> Command {
 }
 
 pub fn get_exe_path<S: AsRef<OsStr>>(program: S) -> PathBuf {
+    if let Some(venv_exe) = program.as_ref().to_str().and_then(venv::get_venv_exe) {
+        return venv_exe;
+    }
+
     let mut program: PathBuf = program.as_ref().into();
 
+    if let Some(name) = program.to_str() {
+        if let Some(version) = get_active_version(name) {
+            if let Ok(version_dir) = get_plugin_version_dir(name, &version) {
+                if let Ok(plugin) = which::which(version_dir.join(name)) {
+                    return plugin;
+                }
+            }
+        }
+    }
+
     if let Ok(plugin_dir) = get_plugin_dir() {
         let plugin = plugin_dir.join(&program);
         if let Ok(plugin) = which::which(plugin) {
@@ -141,13 +414,9 @@ pub fn get_exe_path<S: AsRef<OsStr>>(program: S) -> PathBuf {
 }
 
 pub fn get_plugin_dir() -> Result<PathBuf> {
-    if let Some(user_dir) = directories::UserDirs::new() {
-        let home_dir = user_dir.home_dir();
-        let path = home_dir.join(".ugdown/plugins");
-        if path.is_dir() == false {
-            let _ = std::fs::create_dir_all(&path);
-        }
-        return Ok(path);
+    let path = get_app_dir()?.join("plugins");
+    if path.is_dir() == false {
+        let _ = std::fs::create_dir_all(&path);
     }
-    Err(anyhow::anyhow!("Unable to local plugin dir"))
+    Ok(path)
 }
@@ -0,0 +1,360 @@
+use anyhow::{anyhow, Result};
+
+use super::*;
+
+/// Stream preference expression, e.g. `best[height<=1080][ext=mp4] / best`.
+///
+/// Alternatives are separated by `/` and the first one selecting any stream wins.
+/// Each alternative is a selector, `best`, `worst`, `all` or a stream id or name,
+/// followed by filters `[field op value]`. Fields are `width`, `height`, `fps`,
+/// `bitrate` (kbps), `size` (bytes, `K`/`M`/`G` suffix allowed), `duration`, `ext`,
+/// `name`, `id`, `engine`, `vcodec`, `acodec` and `language`; ops are `=`, `!=`, `<`, `<=`, `>`, `>=` and `*=` (contains).
+#[derive(Debug, PartialEq)]
+pub struct Preference(Vec<Alternative>);
+
+#[derive(Debug, PartialEq)]
+struct Alternative {
+    selector: Selector,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Selector {
+    Best,
+    Worst,
+    All,
+    Named(String),
+}
+
+#[derive(Debug, PartialEq)]
+struct Filter {
+    field: String,
+    op: String,
+    value: String,
+}
+
+impl Preference {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let alternatives = split_outside_brackets(expr)
+            .iter()
+            .map(|x| Alternative::parse(x))
+            .collect::<Result<Vec<Alternative>>>()?;
+
+        match alternatives.len() {
+            0 => Err(anyhow!("Empty stream preference")),
+            _ => Ok(Self(alternatives)),
+        }
+    }
+
+    /// Returns indices of the selected streams in `infos`.
+    pub fn select(&self, infos: &[&DownloadInfo]) -> Vec<usize> {
+        for alternative in &self.0 {
+            let selected = alternative.select(infos);
+            if selected.len() > 0 {
+                return selected;
+            }
+        }
+        Vec::new()
+    }
+}
+
+fn split_outside_brackets(expr: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for c in expr.chars() {
+        match c {
+            '[' => depth = depth + 1,
+            ']' => depth = depth - 1,
+            '/' if depth == 0 => {
+                parts.push(current.trim().to_owned());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    parts.push(current.trim().to_owned());
+    parts.into_iter().filter(|x| x.len() > 0).collect()
+}
+
+impl Alternative {
+    fn parse(expr: &str) -> Result<Self> {
+        let (selector, mut rest) = match expr.find('[') {
+            Some(index) => (expr[..index].trim(), &expr[index..]),
+            None => (expr.trim(), ""),
+        };
+
+        let selector = match selector.to_ascii_lowercase().as_str() {
+            "" | "best" => Selector::Best,
+            "worst" => Selector::Worst,
+            "all" => Selector::All,
+            _ => Selector::Named(selector.to_owned()),
+        };
+
+        let mut filters = Vec::new();
+        while rest.len() > 0 {
+            let end = rest
+                .find(']')
+                .ok_or_else(|| anyhow!("Missing ] in {}", expr))?;
+            filters.push(Filter::parse(&rest[1..end])?);
+            rest = rest[end + 1..].trim_start();
+            if rest.len() > 0 && rest.starts_with('[') == false {
+                return Err(anyhow!("Unexpected {} in {}", rest, expr));
+            }
+        }
+
+        Ok(Self { selector, filters })
+    }
+
+    fn select(&self, infos: &[&DownloadInfo]) -> Vec<usize> {
+        let mut matched: Vec<usize> = (0..infos.len())
+            .filter(|i| self.filters.iter().all(|x| x.is_match(infos[*i])))
+            .collect();
+
+        let rank = |i: &usize| {
+            let info = infos[*i];
+            (info.height.unwrap_or(0), info.bitrate.unwrap_or(0), info.stream_size)
+        };
+
+        match &self.selector {
+            Selector::All => matched,
+            Selector::Best => matched
+                .iter()
+                // Streams with both tracks, or unknown ones, come before video or audio only
+                .max_by_key(|x| (infos[**x].is_muxed(), rank(x)))
+                .cloned()
+                .into_iter()
+                .collect(),
+            Selector::Worst => matched
+                .iter()
+                .min_by_key(|x| (!infos[**x].is_muxed(), rank(x)))
+                .cloned()
+                .into_iter()
+                .collect(),
+            Selector::Named(name) => {
+                matched.retain(|i| {
+                    infos[*i].stream_id.eq_ignore_ascii_case(name)
+                        || infos[*i].stream_name.eq_ignore_ascii_case(name)
+                });
+                matched.truncate(1);
+                matched
+            }
+        }
+    }
+}
+
+impl Filter {
+    fn parse(expr: &str) -> Result<Self> {
+        let caps = FILTER_REGEX
+            .captures(expr)
+            .ok_or_else(|| anyhow!("Invalid filter [{}]", expr))?;
+
+        let field = caps["field"].to_ascii_lowercase();
+        if FILTER_FIELDS.contains(&field.as_str()) == false {
+            return Err(anyhow!("Unknown field {}", field));
+        }
+
+        Ok(Self {
+            field,
+            op: caps["op"].to_owned(),
+            value: caps["value"].to_owned(),
+        })
+    }
+
+    fn is_match(&self, info: &DownloadInfo) -> bool {
+        let number = match self.field.as_str() {
+            "width" => Some(info.width.map(|x| x as f64)),
+            "height" => Some(info.height.map(|x| x as f64)),
+            "fps" => Some(info.fps),
+            "bitrate" => Some(info.bitrate.map(|x| x as f64)),
+            "size" => Some(Some(info.stream_size as f64).filter(|x| *x > 0.0)),
+            "duration" => Some(info.duration),
+            _ => None,
+        };
+
+        if let Some(number) = number {
+            // Unknown numbers never match
+            let (number, value) = match (number, parse_number(&self.value)) {
+                (Some(number), Some(value)) => (number, value),
+                _ => return false,
+            };
+            return match self.op.as_str() {
+                "=" => number == value,
+                "!=" => number != value,
+                "<" => number < value,
+                "<=" => number <= value,
+                ">" => number > value,
+                ">=" => number >= value,
+                _ => false,
+            };
+        }
+
+        let text = match self.field.as_str() {
+            "ext" => Some(&info.ext),
+            "name" => Some(&info.stream_name),
+            "id" => Some(&info.stream_id),
+            "vcodec" => info.vcodec.as_ref(),
+            "acodec" => info.acodec.as_ref(),
+            "language" => info.language.as_ref(),
+            _ => Some(&info.downloader),
+        };
+        let text = match text {
+            Some(text) => text.to_ascii_lowercase(),
+            None => return false,
+        };
+        let value = self.value.to_ascii_lowercase();
+
+        match self.op.as_str() {
+            "=" => text == value,
+            "!=" => text != value,
+            "*=" => text.contains(&value),
+            _ => false,
+        }
+    }
+}
+
+const FILTER_FIELDS: [&str; 13] = [
+    "width", "height", "fps", "bitrate", "size", "duration", "ext", "name", "id", "engine", "vcodec",
+    "acodec", "language",
+];
+
+fn parse_number(value: &str) -> Option<f64> {
+    let value = value.trim().to_ascii_uppercase();
+    let (value, unit) = match value.chars().last()? {
+        'K' => (&value[..value.len() - 1], 1000.0),
+        'M' => (&value[..value.len() - 1], 1000.0 * 1000.0),
+        'G' => (&value[..value.len() - 1], 1000.0 * 1000.0 * 1000.0),
+        _ => (value.as_str(), 1.0),
+    };
+    value.trim().parse::<f64>().ok().map(|x| x * unit)
+}
+
+/// Fills metadata the engine does not report from its quality string, e.g. `1080P60`.
+pub fn normalize_quality(info: &mut DownloadInfo) {
+    let quality = info.stream_name.clone();
+
+    if let Some(caps) = RESOLUTION_REGEX.captures(&quality) {
+        info.width = info.width.or(caps["width"].parse().ok());
+        info.height = info.height.or(caps["height"].parse().ok());
+    }
+
+    if let Some(caps) = HEIGHT_REGEX.captures(&quality) {
+        info.height = info.height.or(caps["height"].parse().ok());
+        info.fps = info.fps.or(caps.name("fps").and_then(|x| x.as_str().parse().ok()));
+    }
+
+    if info.height.is_none() {
+        info.height = NAMED_RESOLUTION_REGEX.captures(&quality).and_then(|caps| {
+            match caps["name"].to_ascii_uppercase().as_str() {
+                "8K" => Some(4320),
+                "4K" => Some(2160),
+                "2K" => Some(1440),
+                _ => None,
+            }
+        });
+    }
+
+    if info.bitrate.is_none() {
+        info.bitrate = BITRATE_REGEX
+            .captures(&quality)
+            .and_then(|x| x["bitrate"].parse().ok());
+    }
+
+    // Codec "none" means the track is absent
+    if info.has_video.is_none() {
+        info.has_video = info.vcodec.as_ref().map(|x| x != "none");
+    }
+    if info.has_audio.is_none() {
+        info.has_audio = info.acodec.as_ref().map(|x| x != "none");
+    }
+    let quality = quality.to_ascii_lowercase();
+    if quality.contains("audio only") || quality.contains("audio_only") {
+        info.has_video = Some(false);
+        info.has_audio = Some(true);
+    } else if quality.contains("video only") {
+        info.has_video = Some(true);
+        info.has_audio = Some(false);
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref FILTER_REGEX: regex::Regex =
+        regex::Regex::new(r"^\s*(?<field>\w+)\s*(?<op><=|>=|!=|\*=|<|>|=)\s*(?<value>.*?)\s*$").unwrap();
+    static ref RESOLUTION_REGEX: regex::Regex =
+        regex::Regex::new(r"(?<width>\d{3,5})x(?<height>\d{3,5})").unwrap();
+    static ref HEIGHT_REGEX: regex::Regex =
+        regex::Regex::new(r"(?<height>\d{3,4})[pPiI](?<fps>\d{2,3})?").unwrap();
+    static ref NAMED_RESOLUTION_REGEX: regex::Regex = regex::Regex::new(r"(?i)\b(?<name>[248]k)\b").unwrap();
+    static ref BITRATE_REGEX: regex::Regex =
+        regex::Regex::new(r"(?i)(?<bitrate>\d{2,5})\s*(kbps|kb/s|k)\b").unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(id: &str, name: &str, ext: &str, size: usize) -> DownloadInfo {
+        let mut info = DownloadInfo {
+            stream_id: id.to_owned(),
+            stream_name: name.to_owned(),
+            ext: ext.to_owned(),
+            stream_size: size,
+            ..Default::default()
+        };
+        normalize_quality(&mut info);
+        info
+    }
+
+    #[test]
+    fn test_normalize_quality() {
+        let info = stream("137", "137 - 1920x1080 (1080p)", "mp4", 0);
+        assert_eq!((Some(1920), Some(1080)), (info.width, info.height));
+        assert_eq!(Some(1080), stream("80", "高清 1080P", "flv", 0).height);
+        assert_eq!(Some(2160), stream("120", "超清 4K", "flv", 0).height);
+        assert_eq!(Some(60.0), stream("720p60", "720p60", "ts", 0).fps);
+        assert_eq!(Some(128), stream("a", "audio 128k", "m4a", 0).bitrate);
+
+        let info = stream("140", "140 - audio only (tiny)", "m4a", 0);
+        assert_eq!((None, Some(false)), (info.height, info.has_video));
+    }
+
+    #[test]
+    fn test_select() {
+        let streams = [
+            stream("1", "2160p", "webm", 900),
+            stream("2", "1080p", "mp4", 500),
+            stream("3", "1080p", "webm", 400),
+            stream("4", "720p", "mp4", 200),
+        ];
+        let infos: Vec<&DownloadInfo> = streams.iter().collect();
+        let select = |expr| Preference::parse(expr).unwrap().select(&infos);
+
+        assert_eq!(vec![0], select("best"));
+        assert_eq!(vec![3], select("worst"));
+        assert_eq!(vec![1], select("best[height<=1080][ext=mp4] / best"));
+        assert_eq!(vec![0], select("best[height<=480] / best"));
+        assert_eq!(vec![1, 3], select("all[ext=mp4]"));
+        assert_eq!(vec![2], select("3"));
+        assert_eq!(vec![3], select("best[size<0.3K]"));
+        assert!(Preference::parse("best[quality>30]").is_err());
+
+        // A video only stream loses to a lower one with audio
+        let mut video_only = stream("137", "1080p", "mp4", 800);
+        video_only.has_video = Some(true);
+        video_only.has_audio = Some(false);
+        let mut audio_only = stream("140", "audio only", "m4a", 50);
+        audio_only.has_video = Some(false);
+        let mut muxed = stream("22", "720p", "mp4", 300);
+        muxed.has_video = Some(true);
+        muxed.has_audio = Some(true);
+        let streams = [video_only, audio_only, muxed];
+        let infos: Vec<&DownloadInfo> = streams.iter().collect();
+        let select = |expr| Preference::parse(expr).unwrap().select(&infos);
+        assert_eq!(vec![2], select("best"));
+        assert_eq!(vec![2], select("worst"));
+        assert_eq!(vec![0], select("best[ext=mp4][height>720]"));
+        assert!(Preference::parse("best[height<=1080").is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::Path,
+    process::{Child, Stdio},
+};
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::proxy::{get_proxy, get_proxy_envs};
+use super::*;
+
+const PROTOCOL_PREFIX: &str = "ugdown-";
+
+/// Executables in plugin dir named `ugdown-<name>[.ext]` speak the protocol below.
+///
+/// ugdown writes one JSON request line to stdin and reads JSON lines from stdout:
+///
+/// - `{"method": "capabilities"}` -> `{"version": "..."}`
+/// - `{"method": "info", "url": "...", "cookie_file": "...", "proxy": "..."}` ->
+///   `{"title": "...", "site": "...", "streams": [{"id", "name", "ext", "size", ...}]}`,
+///   streams may also have `width`, `height`, `fps`, `vcodec`, `acodec`, `bitrate`,
+///   `duration` and `language`
+/// - `{"method": "download", "url": "...", "stream": "...", "output": "...", "cookie_file": "...",
+///   "rate_limit": 1000000, "proxy": "..."}` ->
+///   `{"event": "progress", "downloaded": 1, "total": 2}` ... `{"event": "finished"}`
+///
+/// Capabilities and info responses may be `{"error": "..."}` instead, while downloads
+/// fail with an `{"event": "error", "message": "..."}` line.
+pub struct ProtocolEngine {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct CapabilitiesResponse {
+    version: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InfoResponse {
+    title: String,
+    #[serde(default)]
+    site: String,
+    streams: Vec<ProtocolStream>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProtocolStream {
+    id: String,
+    name: Option<String>,
+    ext: String,
+    #[serde(default)]
+    size: usize,
+    width: Option<u32>,
+    height: Option<u32>,
+    fps: Option<f64>,
+    vcodec: Option<String>,
+    acodec: Option<String>,
+    bitrate: Option<u32>,
+    duration: Option<f64>,
+    language: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+enum ProtocolEvent {
+    Progress { downloaded: u64, total: u64 },
+    Finished,
+    Error { message: String },
+}
+
+/// Returns the protocol engines found in plugin dir.
+pub fn get_protocol_engines() -> Vec<ProtocolEngine> {
+    let plugin_dir = match get_plugin_dir() {
+        Ok(plugin_dir) => plugin_dir,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut engines: Vec<ProtocolEngine> = std::fs::read_dir(plugin_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|x| x.ok())
+                .map(|x| x.path())
+                .filter(|x| x.is_file())
+                .filter_map(|path| {
+                    let stem = path.file_stem()?.to_string_lossy().to_string();
+                    let name = stem.strip_prefix(PROTOCOL_PREFIX)?.to_owned();
+                    Some(ProtocolEngine { name, path })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    engines.sort_by(|a, b| a.name.cmp(&b.name));
+    engines
+}
+
+impl ProtocolEngine {
+    fn command(&self) -> Result<Command> {
+        let is_python = self
+            .path
+            .extension()
+            .map(|x| x == "py")
+            .unwrap_or(false);
+
+        let command = match is_python {
+            true => {
+                let mut command = create_hide_window_command(venv::find_python()?);
+                command.arg(&self.path);
+                command
+            }
+            false => create_hide_window_command(&self.path),
+        };
+
+        Ok(command)
+    }
+
+    fn spawn(&self, request: serde_json::Value, stdout: Stdio) -> Result<Child> {
+        let url = request["url"].as_str().unwrap_or_default();
+        let mut child = self
+            .command()?
+            .envs(get_proxy_envs(url))
+            .stdin(Stdio::piped())
+            .stdout(stdout)
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            writeln!(stdin, "{}", request)?;
+        }
+
+        Ok(child)
+    }
+
+    fn request<T: for<'de> Deserialize<'de>>(&self, request: serde_json::Value) -> Result<T> {
+        let output = self.spawn(request, Stdio::piped())?.wait_with_output()?;
+        let output = String::from_utf8(output.stdout)?;
+
+        let response: serde_json::Value = output
+            .lines()
+            .filter_map(|x| serde_json::from_str(x).ok())
+            .last()
+            .ok_or_else(|| anyhow::anyhow!("{} returns no response", self.name))?;
+
+        if let Some(error) = response.get("error").and_then(|x| x.as_str()) {
+            return Err(anyhow::anyhow!("{}", error));
+        }
+
+        Ok(serde_json::from_value(response)?)
+    }
+}
+
+impl Downloader for ProtocolEngine {
+    fn get_downloader_name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn get_stream_info(
+        &self,
+        url: &str,
+        cookie_file: Option<&Path>,
+    ) -> Result<HashMap<String, DownloadInfo>> {
+        let response: InfoResponse = self.request(json!({
+            "method": "info",
+            "url": url,
+            "cookie_file": cookie_file,
+            "proxy": get_proxy(url),
+        }))?;
+
+        let mut info_map = HashMap::new();
+
+        for stream in response.streams {
+            let info = DownloadInfo {
+                url: url.to_string(),
+                site: response.site.clone(),
+                title: response.title.clone(),
+                ext: stream.ext.clone(),
+                stream_id: stream.id.clone(),
+                stream_name: stream.name.unwrap_or(stream.id.clone()),
+                stream_size: stream.size,
+                downloader: self.get_downloader_name(),
+                width: stream.width,
+                height: stream.height,
+                fps: stream.fps,
+                vcodec: stream.vcodec,
+                acodec: stream.acodec,
+                bitrate: stream.bitrate,
+                duration: stream.duration,
+                language: stream.language,
+                ..Default::default()
+            };
+
+            info_map.insert(stream.id, info);
+        }
+
+        Ok(info_map)
+    }
+
+    fn execute_download(
+        &self,
+        url: &str,
+        id: &str,
+        output_dir: &str,
+        output_name: &str,
+        cookie_file: Option<&Path>,
+        rate_limit: Option<u64>,
+    ) -> Result<Child> {
+        let output = Path::new(output_dir).join(output_name);
+
+        self.spawn(
+            json!({
+                "method": "download",
+                "url": url,
+                "stream": id,
+                "output": output,
+                "cookie_file": cookie_file,
+                "rate_limit": rate_limit,
+                "proxy": get_proxy(url),
+            }),
+            Stdio::piped(),
+        )
+    }
+
+    fn is_stderr_output(&self) -> bool {
+        false
+    }
+
+    fn parse_progress(&self, line: &str) -> Option<Progress> {
+        match serde_json::from_str(line).ok()? {
+            ProtocolEvent::Progress { downloaded, total } if total > 0 => {
+                Some(Progress::Percent(downloaded as f64 / total as f64))
+            }
+            ProtocolEvent::Finished => Some(Progress::Percent(1.0)),
+            ProtocolEvent::Error { message } => Some(Progress::Failed(message)),
+            _ => None,
+        }
+    }
+
+    fn get_program(&self) -> Result<(PathBuf, String)> {
+        let response: CapabilitiesResponse = self.request(json!({ "method": "capabilities" }))?;
+        let version = response.version.unwrap_or("Unknown".to_owned());
+        Ok((self.path.clone(), version))
+    }
+}
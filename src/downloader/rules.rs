@@ -0,0 +1,154 @@
+use crate::config::{get_config, DomainRule};
+
+use super::*;
+
+/// Returns the first rule in config matching `url`.
+pub fn get_rule(url: &str) -> Option<DomainRule> {
+    find_rule(&get_config().rules, url).cloned()
+}
+
+pub fn find_rule<'a>(rules: &'a [DomainRule], url: &str) -> Option<&'a DomainRule> {
+    rules.iter().find(|x| is_rule_match(&x.pattern, url))
+}
+
+/// Returns whether `url` matches a rule in config or a site supported by any engine.
+pub fn is_supported_url(url: &str) -> bool {
+    get_rule(url).is_some()
+        || load_manifests()
+            .iter()
+            .any(|x| x.sites.iter().any(|pattern| is_rule_match(pattern, url)))
+}
+
+pub(super) fn is_rule_match(pattern: &str, url: &str) -> bool {
+    if let Some(pattern) = pattern.strip_prefix("re:") {
+        return regex::Regex::new(pattern)
+            .map(|re| re.is_match(url))
+            .unwrap_or(false);
+    }
+
+    // Urls pasted without scheme are common
+    let host = url::Url::parse(url)
+        .or_else(|_| url::Url::parse(&format!("https://{}", url)))
+        .ok()
+        .and_then(|x| x.host_str().map(|x| x.to_ascii_lowercase()));
+    let host = match host {
+        Some(host) => host,
+        None => return false,
+    };
+
+    let pattern = pattern.to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        // `*.example.com` also matches `example.com`
+        Some(domain) => host == domain || host.ends_with(&format!(".{}", domain)),
+        None => host == pattern,
+    }
+}
+
+/// Selects streams of one url by `preference`, with save options and cookies filled
+/// from `rule`, falling back to `output_dir`.
+pub fn select_tasks(
+    info_map: &HashMap<String, DownloadInfo>,
+    preference: &Preference,
+    rule: &DomainRule,
+    output_dir: &str,
+) -> Vec<DownloadInfo> {
+    let mut streams: Vec<&DownloadInfo> = info_map.values().collect();
+    streams.sort_by(|a, b| a.stream_id.cmp(&b.stream_id));
+    let cookies = rule
+        .cookie_file
+        .as_ref()
+        .and_then(|x| std::fs::read_to_string(x).ok());
+
+    preference
+        .select(&streams)
+        .into_iter()
+        .map(|i| {
+            let mut info = streams[i].clone();
+            let file_name = match rule.file_name.as_ref() {
+                Some(template) => expand_file_name(template, &info),
+                None => format!("{}[{}]", info.title, info.stream_name),
+            };
+            info.save_option = Some(SaveOption {
+                output_dir: rule.output_dir.clone().unwrap_or(output_dir.to_owned()),
+                file_name,
+            });
+            info.cookies = cookies.clone();
+            info
+        })
+        .collect()
+}
+
+/// Expands `{title}`, `{stream_name}`, `{stream_id}`, `{site}` and `{ext}` in `template`.
+pub fn expand_file_name(template: &str, info: &DownloadInfo) -> String {
+    [
+        ("title", &info.title),
+        ("stream_name", &info.stream_name),
+        ("stream_id", &info.stream_id),
+        ("site", &info.site),
+        ("ext", &info.ext),
+    ]
+    .iter()
+    .fold(template.to_owned(), |name, (key, value)| {
+        name.replace(&format!("{{{}}}", key), value)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_rule() {
+        let rules: Vec<DomainRule> = [
+            "*.bilibili.com | lux",
+            "youtu.be | yt-dlp",
+            r"re:^https?://x\.com/ | gallery-dl",
+        ]
+        .iter()
+        .filter_map(|x| DomainRule::from_line(x))
+        .collect();
+
+        let engine = |url| find_rule(&rules, url).and_then(|x| x.engine.clone());
+        assert_eq!(Some("lux".to_owned()), engine("https://www.bilibili.com/video/BV1"));
+        assert_eq!(Some("lux".to_owned()), engine("bilibili.com/video/BV1"));
+        assert_eq!(Some("yt-dlp".to_owned()), engine("https://youtu.be/abc"));
+        assert_eq!(Some("gallery-dl".to_owned()), engine("https://x.com/user"));
+        assert_eq!(None, engine("https://notbilibili.com/"));
+    }
+
+    #[test]
+    fn test_builtin_sites() {
+        // Only the bundled manifests, user ones in ~/.ugdown/engines would change the result
+        let manifests: Vec<EngineManifest> = manifest::BUILTIN_MANIFESTS
+            .iter()
+            .map(|x| toml::from_str(x).unwrap())
+            .collect();
+        let engines = |url: &str| -> Vec<String> {
+            manifests
+                .iter()
+                .filter(|x| x.sites.iter().any(|pattern| is_rule_match(pattern, url)))
+                .map(|x| x.name.clone())
+                .collect()
+        };
+        assert!(engines("https://www.youtube.com/watch?v=abc").contains(&"yt-dlp".to_owned()));
+        assert_eq!(vec!["streamlink", "yt-dlp"], engines("https://www.twitch.tv/abc"));
+        assert!(engines("https://example.com/video").is_empty());
+    }
+
+    #[test]
+    fn test_rule_line() {
+        let rule = DomainRule::from_line("*.example.com | lux |  |  | /tmp | {site}-{title}").unwrap();
+        assert_eq!(None, rule.stream);
+        assert_eq!(Some("/tmp".to_owned()), rule.output_dir);
+        assert_eq!("*.example.com | lux |  |  | /tmp | {site}-{title}", rule.to_line());
+
+        let line = r"re:^https://(www\.)?(youtube\|youtu\.be)/ | yt-dlp | best | a\b\\|c.txt";
+        let rule = DomainRule::from_line(line).unwrap();
+        assert_eq!(r"re:^https://(www\.)?(youtube|youtu\.be)/", rule.pattern);
+        assert_eq!(Some("yt-dlp".to_owned()), rule.engine);
+        assert_eq!(Some("best".to_owned()), rule.stream);
+        assert_eq!(Some(r"a\b\|c.txt".to_owned()), rule.cookie_file);
+        assert_eq!(line, rule.to_line());
+        assert_eq!(Some(rule.clone()), DomainRule::from_line(&rule.to_line()));
+    }
+}
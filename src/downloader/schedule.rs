@@ -0,0 +1,121 @@
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDateTime, NaiveTime};
+
+/// Local times of day between which downloads may run, e.g. `23:00-07:00`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub from: NaiveTime,
+    pub to: NaiveTime,
+}
+
+impl TimeWindow {
+    pub fn parse(text: &str) -> Result<Self> {
+        let (from, to) = text
+            .split_once('-')
+            .ok_or_else(|| anyhow!("{} should be like 23:00-07:00", text.trim()))?;
+        let parse_time = |x: &str| {
+            NaiveTime::parse_from_str(x.trim(), "%H:%M")
+                .map_err(|_| anyhow!("{} should be HH:MM", x.trim()))
+        };
+        Ok(Self {
+            from: parse_time(from)?,
+            to: parse_time(to)?,
+        })
+    }
+
+    /// Windows ending before they start span midnight, equal ends mean all day.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.from == self.to {
+            return true;
+        }
+        match self.from < self.to {
+            true => self.from <= time && time < self.to,
+            false => self.from <= time || time < self.to,
+        }
+    }
+
+    pub fn to_string(&self) -> String {
+        format!("{}-{}", self.from.format("%H:%M"), self.to.format("%H:%M"))
+    }
+}
+
+/// Keeps a task waiting until `start_at`, and running only inside `window`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TaskSchedule {
+    pub start_at: Option<NaiveDateTime>,
+    pub window: Option<TimeWindow>,
+}
+
+impl TaskSchedule {
+    /// Gets the next local date time at `time`, today or tomorrow.
+    pub fn next_time(time: NaiveTime) -> NaiveDateTime {
+        let now = chrono::Local::now().naive_local();
+        let start_at = now.date().and_time(time);
+        match start_at <= now {
+            true => start_at + chrono::Duration::days(1),
+            false => start_at,
+        }
+    }
+
+    pub fn can_run(&self, now: NaiveDateTime) -> bool {
+        self.start_at.map(|x| now >= x).unwrap_or(true)
+            && self.window.map(|x| x.contains(now.time())).unwrap_or(true)
+    }
+
+    /// Describes what the task waits for, e.g. `Waits 02:00`.
+    pub fn to_string(&self, now: NaiveDateTime) -> String {
+        match (self.start_at, self.window) {
+            (Some(start_at), _) if now < start_at => {
+                format!("Waits {}", start_at.format("%H:%M"))
+            }
+            (_, Some(window)) => format!("Runs {}", window.to_string()),
+            _ => String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_window() {
+        let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+
+        let night = TimeWindow::parse("23:00-07:00").unwrap();
+        assert!(night.contains(time(23, 0)));
+        assert!(night.contains(time(2, 30)));
+        assert!(night.contains(time(7, 0)) == false);
+        assert!(night.contains(time(12, 0)) == false);
+        assert_eq!("23:00-07:00", night.to_string());
+
+        let lunch = TimeWindow::parse(" 12:00 - 13:30 ").unwrap();
+        assert!(lunch.contains(time(12, 45)));
+        assert!(lunch.contains(time(13, 30)) == false);
+
+        assert!(TimeWindow::parse("00:00-00:00").unwrap().contains(time(9, 0)));
+        assert!(TimeWindow::parse("23:00").is_err());
+        assert!(TimeWindow::parse("25:00-07:00").is_err());
+    }
+
+    #[test]
+    fn test_task_schedule() {
+        let at = |d, h, m| {
+            chrono::NaiveDate::from_ymd_opt(2024, 5, d)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        };
+        let schedule = TaskSchedule {
+            start_at: Some(at(20, 2, 0)),
+            window: Some(TimeWindow::parse("01:00-06:00").unwrap()),
+        };
+        assert!(schedule.can_run(at(20, 1, 30)) == false);
+        assert!(schedule.can_run(at(20, 2, 0)));
+        assert!(schedule.can_run(at(20, 6, 30)) == false);
+        assert!(schedule.can_run(at(21, 1, 30)));
+        assert_eq!("Waits 02:00", schedule.to_string(at(20, 1, 30)));
+        assert_eq!("Runs 01:00-06:00", schedule.to_string(at(20, 6, 30)));
+        assert!(TaskSchedule::default().can_run(at(20, 12, 0)));
+    }
+}
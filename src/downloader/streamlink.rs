@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use super::*;
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+struct StreamlinkNode {
+    plugin: Option<String>,
+    metadata: Option<StreamlinkMetadata>,
+    #[serde(default)]
+    streams: HashMap<String, StreamlinkStreamNode>,
+    error: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+struct StreamlinkMetadata {
+    author: Option<String>,
+    title: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+struct StreamlinkStreamNode {
+    #[serde(rename = "type")]
+    type_: String,
+    url: Option<String>,
+}
+
+/// Parses `streamlink --json` output, one stream for each quality.
+pub fn parse_stream_info(
+    url: &str,
+    downloader: &str,
+    output: &str,
+) -> Result<HashMap<String, DownloadInfo>> {
+    let node: StreamlinkNode = serde_json::from_str(output)?;
+
+    if let Some(error) = node.error {
+        return Err(anyhow::anyhow!("{}", error));
+    }
+
+    let site = node.plugin.unwrap_or("Unknown".to_owned());
+    let title = node
+        .metadata
+        .and_then(|x| x.title.or(x.author))
+        .unwrap_or("Unknown".to_owned());
+
+    let mut info_map = HashMap::new();
+
+    for (quality, _stream_node) in node.streams {
+        let info = DownloadInfo {
+            url: url.to_string(),
+            site: site.clone(),
+            title: title.clone(),
+            ext: "ts".to_owned(),
+            stream_id: quality.clone(),
+            stream_name: quality.clone(),
+            downloader: downloader.to_owned(),
+            is_live: true,
+            ..Default::default()
+        };
+
+        info_map.insert(quality, info);
+    }
+
+    Ok(info_map)
+}
+
+/// Parses `[download] Written 12.3 MiB to video.ts (10s @ 1.2 MiB/s)` into bytes written.
+pub fn parse_written_progress(line: &str) -> Option<Progress> {
+    let caps = WRITTEN_REGEX.captures(line)?;
+    let size = caps.name("size")?.as_str().parse::<f64>().ok()?;
+    let unit = match caps.name("unit")?.as_str() {
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        "TiB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => 1.0,
+    };
+    Some(Progress::Bytes((size * unit) as u64))
+}
+
+lazy_static::lazy_static! {
+    static ref WRITTEN_REGEX: regex::Regex =
+        regex::Regex::new(r"Written (?<size>[0-9\.]+) ?(?<unit>[KMGT]?i?B)").unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_written_progress() {
+        assert_eq!(
+            Some(Progress::Bytes(1536)),
+            parse_written_progress("[download] Written 1.5 KiB to a.ts (1s @ 1.5 KiB/s)")
+        );
+        assert_eq!(None, parse_written_progress("[cli][info] Opening stream: 720p (hls)"));
+    }
+}
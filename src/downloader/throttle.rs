@@ -0,0 +1,176 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::config::get_config;
+
+/// Tasks running now, which share the global cap, set by scheduler.
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static::lazy_static! {
+    /// Global cap shared by all native downloads, in bytes per second.
+    static ref GLOBAL: Mutex<TokenBucket> =
+        Mutex::new(TokenBucket::new(get_config().speed_limit.unwrap_or(0)));
+}
+
+/// Refills `rate` bytes per second up to one second of burst, 0 rate for unlimited.
+#[derive(Clone, Debug)]
+pub struct TokenBucket {
+    rate: u64,
+    /// Goes below zero when more is taken than available, to be waited off.
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: u64) -> Self {
+        Self {
+            rate,
+            tokens: rate as f64,
+            last: Instant::now(),
+        }
+    }
+
+    pub fn get_rate(&self) -> u64 {
+        self.rate
+    }
+
+    pub fn set_rate(&mut self, rate: u64) {
+        self.rate = rate;
+        self.tokens = self.tokens.min(rate as f64);
+    }
+
+    /// Takes `bytes` tokens, returning how long to wait before going on.
+    pub fn take(&mut self, bytes: u64, now: Instant) -> Duration {
+        if self.rate == 0 {
+            self.last = now;
+            return Duration::ZERO;
+        }
+
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * self.rate as f64).min(self.rate as f64);
+        self.tokens = self.tokens - bytes as f64;
+        match self.tokens < 0.0 {
+            true => Duration::from_secs_f64(-self.tokens / self.rate as f64),
+            false => Duration::ZERO,
+        }
+    }
+}
+
+/// Limits one native download by its own limit, or by the global cap otherwise.
+pub struct Throttle {
+    own: Option<TokenBucket>,
+}
+
+impl Throttle {
+    /// `limit` overrides the global cap for this download, `Some(0)` for unlimited.
+    pub fn new(limit: Option<u64>) -> Self {
+        Self {
+            own: limit.map(TokenBucket::new),
+        }
+    }
+
+    /// Gets a read size which keeps waits short, so that stopping stays responsive.
+    pub fn get_chunk_size(&self, max: usize) -> usize {
+        let rate = match self.own.as_ref() {
+            Some(own) => own.get_rate(),
+            None => GLOBAL.lock().unwrap().get_rate(),
+        };
+        match rate {
+            0 => max,
+            rate => max.min((rate as usize / 10).max(1024)),
+        }
+    }
+
+    /// Waits until `bytes` more may be downloaded.
+    pub fn consume(&mut self, bytes: u64) {
+        let now = Instant::now();
+        let wait = match self.own.as_mut() {
+            Some(own) => own.take(bytes, now),
+            None => GLOBAL.lock().unwrap().take(bytes, now),
+        };
+        if wait.is_zero() == false {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+pub fn get_global_limit() -> Option<u64> {
+    match GLOBAL.lock().unwrap().get_rate() {
+        0 => None,
+        rate => Some(rate),
+    }
+}
+
+/// Changes the global cap, native downloads follow it at once.
+pub fn set_global_limit(limit: Option<u64>) {
+    GLOBAL.lock().unwrap().set_rate(limit.unwrap_or(0));
+}
+
+pub fn get_running_count() -> usize {
+    RUNNING.load(Ordering::SeqCst)
+}
+
+/// Changes the number of running tasks, engines started later get their share of
+/// global cap by it.
+pub fn set_running_count(count: usize) {
+    RUNNING.store(count, Ordering::SeqCst);
+}
+
+/// Gets the limit passed to an engine or aria2c process, which can't share the
+/// global bucket, so the global cap is split among the running tasks.
+pub fn get_process_limit(limit: Option<u64>) -> Option<u64> {
+    match limit {
+        Some(0) => None,
+        Some(limit) => Some(limit),
+        None => get_global_limit().map(|x| (x / get_running_count().max(1) as u64).max(1024)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(1024);
+        assert_eq!(Duration::ZERO, bucket.take(1024, start));
+        assert_eq!(Duration::from_millis(500), bucket.take(512, start));
+
+        // Debt is paid off before new tokens are available
+        let later = start + Duration::from_millis(500);
+        assert_eq!(Duration::ZERO, bucket.take(0, later));
+        assert_eq!(Duration::from_millis(250), bucket.take(256, later));
+
+        // Idle time fills at most one second of burst
+        let idle = later + Duration::from_secs(10);
+        assert_eq!(Duration::ZERO, bucket.take(768, idle));
+        assert_eq!(Duration::from_millis(250), bucket.take(512, idle));
+
+        bucket.set_rate(0);
+        assert_eq!(Duration::ZERO, bucket.take(1 << 30, idle));
+    }
+
+    #[test]
+    fn test_get_process_limit() {
+        // Split among running tasks, even with no limit of running slots
+        set_global_limit(Some(4_000_000));
+        set_running_count(4);
+        assert_eq!(Some(1_000_000), get_process_limit(None));
+        set_running_count(0);
+        assert_eq!(Some(4_000_000), get_process_limit(None));
+        set_running_count(10_000);
+        assert_eq!(Some(1024), get_process_limit(None));
+
+        assert_eq!(Some(5), get_process_limit(Some(5)));
+        assert_eq!(None, get_process_limit(Some(0)));
+        set_global_limit(None);
+        assert_eq!(None, get_process_limit(None));
+    }
+}
@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use super::proxy::get_proxy_envs;
+use super::*;
+use crate::config::get_config;
+
+#[cfg(target_os = "windows")]
+const VENV_BIN: &str = "Scripts";
+#[cfg(not(target_os = "windows"))]
+const VENV_BIN: &str = "bin";
+
+pub fn get_venv_dir(name: &str) -> Result<PathBuf> {
+    Ok(get_plugin_dir()?.join("venvs").join(name))
+}
+
+/// Returns the entry point of `program` in the venv with the same name, if installed.
+pub fn get_venv_exe(program: &str) -> Option<PathBuf> {
+    let bin_dir = get_venv_dir(program).ok()?.join(VENV_BIN);
+    which::which(bin_dir.join(program)).ok()
+}
+
+fn get_venv_python(name: &str) -> Result<PathBuf> {
+    let bin_dir = get_venv_dir(name)?.join(VENV_BIN);
+    Ok(which::which(bin_dir.join("python"))?)
+}
+
+pub fn find_python() -> Result<PathBuf> {
+    if let Some(python) = get_config().python_path {
+        return Ok(PathBuf::from(python));
+    }
+    which::which("python3")
+        .or_else(|_| which::which("python"))
+        .map_err(|_| anyhow::anyhow!("Python is not found, please install it or set python path"))
+}
+
+fn run_command(command: &mut Command) -> Result<()> {
+    let output = command.output()?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let message = stderr
+        .lines()
+        .filter(|x| x.trim().len() > 0)
+        .last()
+        .unwrap_or("Unknown error")
+        .to_owned();
+    Err(anyhow::anyhow!("{}", message))
+}
+
+/// Creates the venv if needed and installs (or upgrades) `package` into it.
+///
+/// `version` may be a version number, a path to local wheel, or `None` for latest.
+pub fn install_venv_package(name: &str, package: &str, version: Option<&str>) -> Result<()> {
+    let venv_dir = get_venv_dir(name)?;
+
+    if get_venv_python(name).is_err() {
+        run_command(
+            create_hide_window_command(find_python()?)
+                .arg("-m")
+                .arg("venv")
+                .arg(&venv_dir),
+        )?;
+    }
+
+    let requirement = match version.map(|x| x.trim()) {
+        Some(wheel) if wheel.ends_with(".whl") => wheel.to_owned(),
+        Some(version) if version.len() > 0 => format!("{}=={}", package, version),
+        _ => package.to_owned(),
+    };
+
+    let config = get_config();
+    let mut command = create_hide_window_command(get_venv_python(name)?);
+    command
+        .arg("-m")
+        .arg("pip")
+        .arg("install")
+        .arg("--upgrade")
+        .arg("--disable-pip-version-check");
+
+    let index_url = config
+        .pip_index_url
+        .clone()
+        .unwrap_or("https://pypi.org/simple".to_owned());
+    command.envs(get_proxy_envs(&index_url));
+    if let Some(index_url) = config.pip_index_url {
+        command.arg("--index-url").arg(index_url);
+    }
+    if let Some(find_links) = config.pip_find_links {
+        command.arg("--find-links").arg(find_links);
+    }
+
+    run_command(command.arg(requirement))
+}
+
+pub fn uninstall_venv(name: &str) -> Result<()> {
+    let venv_dir = get_venv_dir(name)?;
+    if venv_dir.is_dir() {
+        std::fs::remove_dir_all(venv_dir)?;
+    }
+    Ok(())
+}
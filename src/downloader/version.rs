@@ -0,0 +1,116 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use super::get_plugin_dir;
+use crate::config::{get_config, set_config};
+
+/// Numeric version components, e.g. `0.19.0` for lux or `2021.12.17` for youtube-dl.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EngineVersion(Vec<u64>);
+
+impl EngineVersion {
+    pub fn parse(version: &str) -> Option<Self> {
+        let re = regex::Regex::new(r"[0-9]+(\.[0-9]+)*").unwrap();
+        let found = re.find(version)?;
+
+        let mut parts: Vec<u64> = found
+            .as_str()
+            .split('.')
+            .map(|x| x.parse::<u64>().unwrap_or(0))
+            .collect();
+
+        while parts.len() > 1 && parts.last() == Some(&0) {
+            parts.pop();
+        }
+
+        Some(Self(parts))
+    }
+}
+
+pub fn is_update_available(local: &str, latest: &str) -> bool {
+    match (EngineVersion::parse(local), EngineVersion::parse(latest)) {
+        (Some(local), Some(latest)) => latest > local,
+        _ => false,
+    }
+}
+
+/// Returns `plugins/<program>/<version>`, where one release of a tool is kept.
+pub fn get_plugin_version_dir(program: &str, version: &str) -> Result<PathBuf> {
+    Ok(get_plugin_dir()?.join(program).join(version))
+}
+
+/// Returns the installed versions of `program`, newest first.
+pub fn get_installed_versions(program: &str) -> Vec<String> {
+    let program_dir = match get_plugin_dir() {
+        Ok(plugin_dir) => plugin_dir.join(program),
+        Err(_) => return Vec::new(),
+    };
+
+    let mut versions: Vec<String> = std::fs::read_dir(program_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|x| x.ok())
+                .filter(|x| x.path().is_dir())
+                .map(|x| x.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    versions.sort_by_key(|x| std::cmp::Reverse(EngineVersion::parse(x)));
+    versions
+}
+
+pub fn get_pinned_version(program: &str) -> Option<String> {
+    get_config().pinned_versions.get(program).cloned()
+}
+
+/// Returns the pinned version if it is still installed, otherwise the newest one.
+pub fn get_active_version(program: &str) -> Option<String> {
+    let versions = get_installed_versions(program);
+    match get_pinned_version(program) {
+        Some(pinned) if versions.contains(&pinned) => Some(pinned),
+        _ => versions.first().cloned(),
+    }
+}
+
+pub fn pin_version(program: &str, version: Option<&str>) -> Result<()> {
+    let mut config = get_config();
+    match version {
+        Some(version) => config
+            .pinned_versions
+            .insert(program.to_owned(), version.to_owned()),
+        None => config.pinned_versions.remove(program),
+    };
+    set_config(config)
+}
+
+/// Pins the installed version right before the active one and returns it.
+pub fn rollback_version(program: &str) -> Result<String> {
+    let versions = get_installed_versions(program);
+    let active = get_active_version(program)
+        .ok_or_else(|| anyhow::anyhow!("No installed version of {}", program))?;
+
+    let previous = versions
+        .iter()
+        .skip_while(|x| **x != active)
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!("No older version of {} to roll back to", program))?;
+
+    pin_version(program, Some(previous))?;
+    Ok(previous.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_update_available() {
+        assert!(is_update_available("0.18.0", "0.19.0"));
+        assert!(is_update_available("2021.06.06", "2021.12.17"));
+        assert!(!is_update_available("2021.12.17", "2021.12.17"));
+        assert!(!is_update_available("0.19", "0.19.0"));
+        assert!(!is_update_available("Unknown", "0.19.0"));
+    }
+}
@@ -1,8 +1,4 @@
-use std::{
-    collections::HashMap,
-    path::Path,
-    process::{Child, Stdio},
-};
+use std::collections::HashMap;
 
 use anyhow::Result;
 use serde::Deserialize;
@@ -12,8 +8,8 @@ use super::*;
 // #[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct YoutuledlNode {
-    // id: String,
-    // duration: f64,
+    id: Option<String>,
+    duration: Option<f64>,
     formats: Option<Vec<YoutuledlFormatNode>>,
     title: String,
     // description: String,
@@ -23,7 +19,7 @@ struct YoutuledlNode {
     // extractor: String,
     webpage_url: String,
     // webpage_url_basename: String,
-    // extractor_key: String,
+    extractor_key: Option<String>,
     // playlist: Option<YoutuledlPlaylist>,
     // playlist_index: Option<String>,
     // thumbnails: Vec<YoutuledlThumbnailNode>,
@@ -39,6 +35,8 @@ struct YoutuledlNode {
     // protocol: String,
     // fulltitle: String,
     _filename: String,
+    #[serde(flatten)]
+    meta: YoutuledlStreamMeta,
 }
 
 // #[allow(dead_code)]
@@ -51,6 +49,31 @@ struct YoutuledlFormatNode {
     format_id: String,
     format: String,
     // protocol: String,
+    #[serde(flatten)]
+    meta: YoutuledlStreamMeta,
+}
+
+#[derive(Debug, Deserialize)]
+struct YoutuledlStreamMeta {
+    width: Option<u32>,
+    height: Option<u32>,
+    fps: Option<f64>,
+    vcodec: Option<String>,
+    acodec: Option<String>,
+    tbr: Option<f64>,
+    language: Option<String>,
+}
+
+impl YoutuledlStreamMeta {
+    fn apply(&self, info: &mut DownloadInfo) {
+        info.width = self.width;
+        info.height = self.height;
+        info.fps = self.fps;
+        info.vcodec = self.vcodec.clone();
+        info.acodec = self.acodec.clone();
+        info.bitrate = self.tbr.map(|x| x as u32);
+        info.language = self.language.clone();
+    }
 }
 
 // #[allow(dead_code)]
@@ -68,131 +91,122 @@ struct YoutuledlFormatNode {
 // #[derive(Debug, Deserialize)]
 // struct YoutuledlSubtitleNode {}
 
-pub struct Youtubedl {}
-
-impl Downloader for Youtubedl {
-    fn get_downloader_name(&self) -> String {
-        "Youtube-dl".to_owned()
-    }
-
-    fn get_stream_info(
-        &self,
-        url: &str,
-        cookie_file: Option<&Path>,
-    ) -> Result<HashMap<String, DownloadInfo>> {
-        let result = match &cookie_file {
-            Some(file) => create_hide_window_command("youtube-dl")
-                .arg("-c")
-                .arg(file)
-                .arg("--socket-timeout")
-                .arg("4")
-                .arg("-j")
-                .arg(url)
-                .output()?,
-            None => create_hide_window_command("youtube-dl")
-                .arg("--socket-timeout")
-                .arg("4")
-                .arg("-j")
-                .arg(url)
-                .output()?,
-        };
-
-        let result = String::from_utf8(result.stdout.to_vec())?;
-        let result: YoutuledlNode = serde_json::from_str(&result)?;
-
-        let mut info_map = HashMap::new();
-
-        let site = &result.webpage_url;
-        let title = &result.title;
-
-        if let Some(formats) = &result.formats {
-            for format_node in formats {
-                let info = DownloadInfo {
-                    url: url.to_string(),
-                    site: site.clone(),
-                    title: title.clone(),
-                    ext: format_node.ext.clone(),
-                    stream_id: format_node.format_id.clone(),
-                    stream_name: format_node.format.clone(),
-                    stream_size: format_node.filesize,
-                    downloader: self.get_downloader_name(),
-                    ..Default::default()
-                };
-
-                info_map.insert(format_node.format_id.clone(), info);
-            }
-        } else {
-            let info = DownloadInfo {
+pub fn parse_stream_info(
+    url: &str,
+    downloader: &str,
+    output: &str,
+) -> Result<HashMap<String, DownloadInfo>> {
+    let result: YoutuledlNode = serde_json::from_str(output)?;
+
+    let mut info_map = HashMap::new();
+
+    let site = &result.webpage_url;
+    let title = &result.title;
+    // The same as lines written by `--download-archive`
+    let archive_id = match (&result.extractor_key, &result.id) {
+        (Some(extractor), Some(id)) => Some(format!("{} {}", extractor.to_lowercase(), id)),
+        _ => None,
+    };
+
+    if let Some(formats) = &result.formats {
+        for format_node in formats {
+            let mut info = DownloadInfo {
                 url: url.to_string(),
                 site: site.clone(),
                 title: title.clone(),
-                ext: result.ext.clone(),
-                stream_id: result.format_id.clone(),
-                stream_size: result.filesize.unwrap_or(0),
-                stream_name: result.format.clone(),
-                downloader: self.get_downloader_name(),
+                ext: format_node.ext.clone(),
+                stream_id: format_node.format_id.clone(),
+                stream_name: format_node.format.clone(),
+                stream_size: format_node.filesize,
+                downloader: downloader.to_owned(),
+                duration: result.duration,
+                archive_id: archive_id.clone(),
                 ..Default::default()
             };
-            info_map.insert(result.format_id.clone(), info);
-        }
+            format_node.meta.apply(&mut info);
 
-        Ok(info_map)
+            info_map.insert(format_node.format_id.clone(), info);
+        }
+    } else {
+        let mut info = DownloadInfo {
+            url: url.to_string(),
+            site: site.clone(),
+            title: title.clone(),
+            ext: result.ext.clone(),
+            stream_id: result.format_id.clone(),
+            stream_size: result.filesize.unwrap_or(0),
+            stream_name: result.format.clone(),
+            downloader: downloader.to_owned(),
+            duration: result.duration,
+            archive_id,
+            ..Default::default()
+        };
+        result.meta.apply(&mut info);
+        info_map.insert(result.format_id.clone(), info);
     }
 
-    fn execute_download(
-        &self,
-        url: &str,
-        id: &str,
-        output_dir: &str,
-        output_name: &str,
-        cookie_file: Option<&Path>,
-    ) -> anyhow::Result<Child> {
-        let output = format!("{}/{}", output_dir, output_name);
-
-        let child = match &cookie_file {
-            Some(cookie_file) => create_hide_window_command("youtube-dl")
-                .arg("-c")
-                .arg(cookie_file)
-                .arg("-f")
-                .arg(id)
-                .arg("-o")
-                .arg(output)
-                .arg(url)
-                .stdin(Stdio::null())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::null())
-                .spawn()?,
-            None => create_hide_window_command("youtube-dl")
-                .arg("-f")
-                .arg(id)
-                .arg("-o")
-                .arg(output)
-                .arg(url)
-                .stdin(Stdio::null())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::null())
-                .spawn()?,
-        };
+    Ok(info_map)
+}
+
+/// Parses output of `--flat-playlist -J`, nested playlists (e.g. tabs of a
+/// channel) are flattened.
+pub fn parse_playlist(output: &str) -> Result<Vec<PlaylistEntry>> {
+    let result: serde_json::Value = serde_json::from_str(output)?;
+    let mut entries = Vec::new();
+    collect_entries(&result, &mut entries);
+    Ok(entries)
+}
 
-        Ok(child)
+fn collect_entries(node: &serde_json::Value, entries: &mut Vec<PlaylistEntry>) {
+    if let Some(children) = node["entries"].as_array() {
+        for child in children {
+            collect_entries(child, entries);
+        }
+        return;
     }
 
-    fn is_stderr_output(&self) -> bool {
-        false
+    let text = |key: &str| node[key].as_str().map(|x| x.to_owned());
+    let (id, ie_key) = (text("id"), text("ie_key").or(text("extractor_key")));
+    // youtube-dl gives bare ids as url of youtube entries
+    let url = text("webpage_url")
+        .or(text("url").filter(|x| x.contains("://")))
+        .or_else(|| match (ie_key.as_deref(), id.as_ref()) {
+            (Some("Youtube"), Some(id)) => Some(format!("https://www.youtube.com/watch?v={}", id)),
+            _ => None,
+        });
+
+    if let Some(url) = url {
+        entries.push(PlaylistEntry {
+            title: text("title").unwrap_or(url.clone()),
+            url,
+            archive_id: match (ie_key, id) {
+                (Some(ie_key), Some(id)) => Some(format!("{} {}", ie_key.to_lowercase(), id)),
+                _ => None,
+            },
+        });
     }
+}
 
-    fn get_program(&self) -> Result<(PathBuf, String)> {
-        let mut command = create_hide_window_command("youtube-dl");
-        let program = which::which(command.get_program())?;
-        let result = command
-            .arg("--version")
-            .output()?;
-        let result = String::from_utf8(result.stdout.to_vec())?;
-        let re = regex::Regex::new(r"([0-9]+\.[0-9]+\.[0-9]+)").unwrap();
-        let version = re
-            .find(&result)
-            .map(|x| x.as_str().to_string())
-            .unwrap_or("Unknown".to_owned());
-        Ok((program, version))
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_playlist() {
+        let output = r#"{"_type": "playlist", "id": "UC1", "entries": [
+            {"_type": "playlist", "title": "Videos", "entries": [
+                {"_type": "url", "ie_key": "Youtube", "id": "aaa", "url": "https://www.youtube.com/watch?v=aaa", "title": "Lecture 2"},
+                {"_type": "url", "ie_key": "Youtube", "id": "bbb", "url": "bbb", "title": "Lecture 1"}
+            ]},
+            {"_type": "url", "ie_key": "Generic", "url": "https://example.com/talk"}
+        ]}"#;
+
+        let entries = parse_playlist(output).unwrap();
+        assert_eq!(3, entries.len());
+        assert_eq!(Some("youtube aaa".to_owned()), entries[0].archive_id);
+        assert_eq!("https://www.youtube.com/watch?v=bbb", entries[1].url);
+        assert_eq!("Lecture 1", entries[1].title);
+        assert_eq!(None, entries[2].archive_id);
+        assert_eq!("https://example.com/talk", entries[2].title);
     }
 }
@@ -0,0 +1,257 @@
+use std::{fs::OpenOptions, io::Write, path::PathBuf, sync::Mutex};
+
+use anyhow::Result;
+use chrono::{NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::get_app_dir, downloader::*};
+
+lazy_static::lazy_static! {
+    /// Tasks finish on their own threads, lines must not interleave.
+    static ref HISTORY_LOCK: Mutex<()> = Mutex::new(());
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Finished,
+    Failed,
+    Stopped,
+}
+
+impl Outcome {
+    pub const NAMES: [&'static str; 3] = ["Finished", "Failed", "Stopped"];
+
+    pub fn to_string(&self) -> String {
+        match self {
+            Self::Finished => "Finished".to_owned(),
+            Self::Failed => "Failed".to_owned(),
+            Self::Stopped => "Stopped".to_owned(),
+        }
+    }
+}
+
+/// One finished, failed or stopped download, appended to `~/.ugdown/history.jsonl`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub url: String,
+    pub title: String,
+    pub site: String,
+    pub engine: String,
+    pub stream_id: String,
+    pub stream_name: String,
+    pub ext: String,
+    pub output_dir: String,
+    pub file_name: String,
+    /// Output file path, engines may have named it differently.
+    pub output: String,
+    pub size: u64,
+    /// Unix timestamps in seconds.
+    pub started_at: i64,
+    pub finished_at: i64,
+    pub outcome: Outcome,
+    pub error: Option<String>,
+}
+
+impl HistoryEntry {
+    /// Creates an entry finishing now, with output and size as planned in `info`.
+    pub fn new(
+        info: &DownloadInfo,
+        started_at: i64,
+        outcome: Outcome,
+        error: Option<String>,
+    ) -> Self {
+        let (output_dir, file_name) = info
+            .save_option
+            .as_ref()
+            .map(|x| (x.output_dir.clone(), x.file_name.clone()))
+            .unwrap_or_default();
+        let output = match info.ext.len() {
+            0 => PathBuf::from(&output_dir).join(&file_name),
+            _ => PathBuf::from(&output_dir).join(format!("{}.{}", file_name, info.ext)),
+        };
+
+        Self {
+            url: info.url.clone(),
+            title: info.title.clone(),
+            site: info.site.clone(),
+            engine: info.downloader.clone(),
+            stream_id: info.stream_id.clone(),
+            stream_name: info.stream_name.clone(),
+            ext: info.ext.clone(),
+            output_dir,
+            file_name,
+            output: output.to_string_lossy().to_string(),
+            size: info.stream_size as u64,
+            started_at,
+            finished_at: chrono::Local::now().timestamp(),
+            outcome,
+            error,
+        }
+    }
+
+    /// Rebuilds the task to download the same stream again. History keeps no
+    /// cookies, so they come from the matching site rule.
+    pub fn to_download_info(&self) -> DownloadInfo {
+        let cookies = get_rule(&self.url)
+            .and_then(|x| x.cookie_file)
+            .and_then(|x| std::fs::read_to_string(x).ok());
+        let parts = match self.engine == DIRECT_ENGINE {
+            true => vec![PartInfo {
+                url: self.url.clone(),
+                size: self.size,
+                ext: self.ext.clone(),
+            }],
+            false => Vec::new(),
+        };
+
+        DownloadInfo {
+            url: self.url.clone(),
+            site: self.site.clone(),
+            title: self.title.clone(),
+            ext: self.ext.clone(),
+            stream_id: self.stream_id.clone(),
+            stream_name: self.stream_name.clone(),
+            stream_size: self.size as usize,
+            downloader: self.engine.clone(),
+            save_option: Some(SaveOption {
+                output_dir: self.output_dir.clone(),
+                file_name: self.file_name.clone(),
+            }),
+            cookies,
+            parts,
+            ..Default::default()
+        }
+    }
+}
+
+fn get_history_file() -> Result<PathBuf> {
+    Ok(get_app_dir()?.join("history.jsonl"))
+}
+
+pub fn append_history(entry: &HistoryEntry) -> Result<()> {
+    let line = serde_json::to_string(entry)?;
+    let _lock = HISTORY_LOCK.lock().unwrap();
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(get_history_file()?)?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
+
+/// Loads all entries oldest first, skipping lines which can't be parsed.
+pub fn load_history() -> Vec<HistoryEntry> {
+    get_history_file()
+        .and_then(|x| Ok(std::fs::read_to_string(x)?))
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(|x| serde_json::from_str(x).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Search conditions of History window, dates are local and inclusive.
+#[derive(Clone, Debug, Default)]
+pub struct HistoryFilter {
+    pub text: String,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub outcome: Option<Outcome>,
+}
+
+impl HistoryFilter {
+    /// Every word of `text` has to appear in url, title, site, engine, stream or
+    /// output path, ignoring case.
+    pub fn is_match(&self, entry: &HistoryEntry) -> bool {
+        if self.outcome.map(|x| x != entry.outcome).unwrap_or(false) {
+            return false;
+        }
+
+        let date = chrono::Local
+            .timestamp_opt(entry.finished_at, 0)
+            .single()
+            .map(|x| x.date_naive());
+        if let (Some(from), Some(date)) = (self.from, date) {
+            if date < from {
+                return false;
+            }
+        }
+        if let (Some(to), Some(date)) = (self.to, date) {
+            if date > to {
+                return false;
+            }
+        }
+
+        let haystack = [
+            &entry.url,
+            &entry.title,
+            &entry.site,
+            &entry.engine,
+            &entry.stream_name,
+            &entry.output,
+        ]
+        .iter()
+        .map(|x| x.to_lowercase())
+        .collect::<Vec<String>>()
+        .join("\n");
+        self.text
+            .to_lowercase()
+            .split_whitespace()
+            .all(|word| haystack.contains(word))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_filter() {
+        let finished_at = chrono::Local
+            .with_ymd_and_hms(2024, 5, 20, 12, 0, 0)
+            .unwrap()
+            .timestamp();
+        let entry = HistoryEntry {
+            url: "https://www.bilibili.com/video/BV1".to_owned(),
+            title: "Rust Tutorial".to_owned(),
+            site: "bilibili".to_owned(),
+            engine: "lux".to_owned(),
+            stream_id: "80".to_owned(),
+            stream_name: "1080P".to_owned(),
+            ext: "mp4".to_owned(),
+            output_dir: "/tmp".to_owned(),
+            file_name: "Rust Tutorial[1080P]".to_owned(),
+            output: "/tmp/Rust Tutorial[1080P].mp4".to_owned(),
+            size: 0,
+            started_at: finished_at - 60,
+            finished_at,
+            outcome: Outcome::Finished,
+            error: None,
+        };
+
+        let filter = |text: &str, from: Option<(i32, u32, u32)>, to: Option<(i32, u32, u32)>| {
+            HistoryFilter {
+                text: text.to_owned(),
+                from: from.and_then(|(y, m, d)| NaiveDate::from_ymd_opt(y, m, d)),
+                to: to.and_then(|(y, m, d)| NaiveDate::from_ymd_opt(y, m, d)),
+                outcome: None,
+            }
+            .is_match(&entry)
+        };
+        assert!(filter("", None, None));
+        assert!(filter("rust BV1", None, None));
+        assert!(filter("1080p lux", Some((2024, 5, 20)), Some((2024, 5, 20))));
+        assert!(filter("rust youtube", None, None) == false);
+        assert!(filter("", Some((2024, 5, 21)), None) == false);
+        assert!(filter("", None, Some((2024, 5, 19))) == false);
+
+        let failed_only = HistoryFilter {
+            outcome: Some(Outcome::Failed),
+            ..Default::default()
+        };
+        assert!(failed_only.is_match(&entry) == false);
+    }
+}
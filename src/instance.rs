@@ -0,0 +1,128 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::Mutex,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{config::get_app_dir, downloader::import_urls, send_message, view::*};
+
+lazy_static::lazy_static! {
+    /// Exclusive lock of `instance.guard`, held by the running instance until it exits.
+    static ref GUARD: Mutex<Option<File>> = Mutex::new(None);
+}
+
+/// Lock file of the running instance, holding its port and a key on two lines.
+fn get_lock_file() -> Result<PathBuf> {
+    Ok(get_app_dir()?.join("instance.lock"))
+}
+
+/// File locked by the running instance. It is not the lock file itself, as locked
+/// files can't be read by others on Windows.
+fn get_guard_file() -> Result<PathBuf> {
+    Ok(get_app_dir()?.join("instance.guard"))
+}
+
+/// Takes the instance lock, returns false if another instance holds it. The OS
+/// drops the lock of a crashed instance.
+fn try_lock() -> Result<bool> {
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .open(get_guard_file()?)?;
+    match file.try_lock() {
+        Ok(()) => {
+            *GUARD.lock().unwrap() = Some(file);
+            // Port of a previous instance may be taken by anything now
+            let _ = std::fs::remove_file(get_lock_file()?);
+            Ok(true)
+        }
+        Err(TryLockError::WouldBlock) => Ok(false),
+        Err(TryLockError::Error(error)) => Err(error.into()),
+    }
+}
+
+/// Sends `args` to the running instance, returns false if there is none and this
+/// one takes its place. Fails if the running one does not respond.
+pub fn forward(args: &[String]) -> Result<bool> {
+    // Without a lock to take, e.g. no app dir, this one runs on its own
+    if try_lock().unwrap_or(true) {
+        return Ok(false);
+    }
+
+    // The running instance may still be starting up and not listening yet
+    for _ in 0..50 {
+        if send(args).is_ok() {
+            return Ok(true);
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    Err(anyhow!(
+        "ugdown is running but does not respond, please close it before starting again"
+    ))
+}
+
+fn send(args: &[String]) -> Result<()> {
+    let content = std::fs::read_to_string(get_lock_file()?)?;
+    let mut lines = content.lines();
+    let (port, key) = match (
+        lines.next().and_then(|x| x.parse::<u16>().ok()),
+        lines.next(),
+    ) {
+        (Some(port), Some(key)) => (port, key),
+        _ => return Err(anyhow!("Lock file is not written yet")),
+    };
+
+    let mut stream =
+        TcpStream::connect_timeout(&([127, 0, 0, 1], port).into(), Duration::from_secs(1))?;
+
+    let mut message = vec![key.to_owned()];
+    message.extend(args.iter().map(|x| x.replace('\n', " ")));
+    stream.write_all(format!("{}\n", message.join("\n")).as_bytes())?;
+    Ok(())
+}
+
+/// Listens for arguments of later invocations, and writes the lock file for them.
+pub fn listen() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    let key = uuid::Uuid::new_v4().simple().to_string();
+    std::fs::write(get_lock_file()?, format!("{}\n{}\n", port, key))?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().filter_map(|x| x.ok()) {
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+            let mut lines = BufReader::new(stream).lines().filter_map(|x| x.ok());
+            if lines.next().as_deref() != Some(key.as_str()) {
+                continue;
+            }
+            open_args(&lines.collect::<Vec<String>>());
+        }
+    });
+
+    Ok(())
+}
+
+/// Removes the lock file and drops the instance lock on exit. The guard file stays,
+/// as a later instance may have opened it already.
+pub fn release() {
+    if let Ok(lock_file) = get_lock_file() {
+        let _ = std::fs::remove_file(lock_file);
+    }
+    GUARD.lock().unwrap().take();
+}
+
+/// Opens urls found in `args` (urls, url list files or `file://` uris), one in
+/// Add Url dialog and more in batch dialog, or just raises main window.
+pub fn open_args(args: &[String]) {
+    let urls = import_urls(&args.join("\n"));
+    match urls.len() {
+        0 => send_message(MainFormMessage::Activate),
+        1 => send_message(AddUrlDialogMessage::ShowUrl(urls[0].clone())),
+        _ => send_message(BatchDialogMessage::Import(urls.join("\n"))),
+    }
+}
@@ -1,6 +1,12 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod archive;
+mod config;
 mod downloader;
+mod history;
+mod instance;
+mod server;
+mod subscription;
 mod view;
 
 use fltk::app::{channel, App, Receiver, Sender};
@@ -16,7 +22,12 @@ pub enum AppMessage {
     MainForm(MainFormMessage),
     AddUrlDialog(AddUrlDialogMessage),
     EngineManager(EngineManagerMessage),
-    ToolDownloader(ToolDownloaderMessage)
+    ToolDownloader(ToolDownloaderMessage),
+    OptionDialog(OptionDialogMessage),
+    BatchDialog(BatchDialogMessage),
+    Clipboard(ClipboardMessage),
+    HistoryDialog(HistoryDialogMessage),
+    Subscription(SubscriptionMessage),
 }
 
 pub fn send_message<T>(message: T)
@@ -27,23 +38,72 @@ where
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|x| x == "--poll-subscriptions") {
+        if let Err(error) = subscription::poll_headless() {
+            println!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    match instance::forward(&args) {
+        Ok(true) => return,
+        Ok(false) => {}
+        // A second instance would fight the running one over ports and files
+        Err(error) => {
+            let _app = App::default();
+            fltk::dialog::alert_default(&error.to_string());
+            return;
+        }
+    }
+
     let app = App::default();
     let mut mainform = MainForm::default();
     let mut add_url_dialog = AddUrlDialog::default();
     let mut engine_manager = EngineManager::default();
     let mut tool_downloader = ToolDownloader::default();
+    let mut option_dialog = OptionDialog::default();
+    let mut batch_dialog = BatchDialog::default();
+    let mut clipboard_monitor = ClipboardMonitor::default();
+    let mut history_dialog = HistoryDialog::default();
+    let mut subscription_manager = SubscriptionManager::default();
 
     let widget_theme = WidgetTheme::new(ThemeType::Metro);
     widget_theme.apply();
 
+    if config::get_config().check_engine_update {
+        check_engine_updates(false);
+    }
+
+    if config::get_config().api_enabled {
+        if let Err(error) = server::start() {
+            println!("{}", error)
+        }
+    }
+
+    if let Err(error) = instance::listen() {
+        println!("{}", error)
+    }
+    if args.len() > 0 {
+        instance::open_args(&args);
+    }
+
     while app.wait() {
         if let Some(message) = CHANNEL.1.recv() {
             match message {
                 AppMessage::MainForm(message) => mainform.handle_message(message),
                 AppMessage::AddUrlDialog(message) => add_url_dialog.handle_message(message),
                 AppMessage::EngineManager(message) => engine_manager.handle_message(message),
-                AppMessage::ToolDownloader(message) => tool_downloader.handle_message(message)
+                AppMessage::ToolDownloader(message) => tool_downloader.handle_message(message),
+                AppMessage::OptionDialog(message) => option_dialog.handle_message(message),
+                AppMessage::BatchDialog(message) => batch_dialog.handle_message(message),
+                AppMessage::Clipboard(message) => clipboard_monitor.handle_message(message),
+                AppMessage::HistoryDialog(message) => history_dialog.handle_message(message),
+                AppMessage::Subscription(message) => subscription_manager.handle_message(message),
             }
         }
     }
+
+    instance::release();
 }
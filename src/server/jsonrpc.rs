@@ -0,0 +1,380 @@
+use std::{io::Cursor, path::Path};
+
+use super::*;
+
+/// Version reported to aria2 clients, some of them check it.
+const ARIA2_VERSION: &str = "1.37.0";
+
+const METHODS: [&str; 14] = [
+    "aria2.addUri",
+    "aria2.remove",
+    "aria2.forceRemove",
+    "aria2.pause",
+    "aria2.forcePause",
+    "aria2.unpause",
+    "aria2.tellStatus",
+    "aria2.tellActive",
+    "aria2.tellWaiting",
+    "aria2.tellStopped",
+    "aria2.getGlobalStat",
+    "aria2.getVersion",
+    "system.multicall",
+    "system.listMethods",
+];
+
+/// Code and message of a failed call.
+type RpcResult = std::result::Result<Value, (i64, String)>;
+
+/// Serves the subset of aria2 JSON-RPC used by browser download extensions, so
+/// they can send links to ugdown as if it was aria2.
+pub fn handle_jsonrpc(request: &mut Request, token: &str) -> Response<Cursor<Vec<u8>>> {
+    let mut body = String::new();
+    let call: Value = match request
+        .as_reader()
+        .read_to_string(&mut body)
+        .ok()
+        .and_then(|_| serde_json::from_str(&body).ok())
+    {
+        Some(call) => call,
+        None => return json_response(400, &error_reply(Value::Null, -32700, "Parse error")),
+    };
+
+    let result = match call {
+        Value::Array(calls) => Value::Array(calls.iter().map(|x| reply(x, token)).collect()),
+        call => reply(&call, token),
+    };
+
+    // aria2 answers failed calls with http 400
+    match result.get("error") {
+        Some(_) => json_response(400, &result),
+        None => json_response(200, &result),
+    }
+}
+
+fn reply(call: &Value, token: &str) -> Value {
+    let id = call["id"].clone();
+    let method = call["method"].as_str().unwrap_or("");
+    let params = call["params"].as_array().cloned().unwrap_or_default();
+    match dispatch(method, params, token) {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => error_reply(id, code, &message),
+    }
+}
+
+fn error_reply(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+fn dispatch(method: &str, mut params: Vec<Value>, token: &str) -> RpcResult {
+    // Only `aria2.*` methods carry the secret
+    if method.starts_with("aria2.") {
+        let secret = format!("token:{}", token);
+        if params.first().and_then(|x| x.as_str()) != Some(secret.as_str()) {
+            return Err((1, "Unauthorized".to_owned()));
+        }
+        params.remove(0);
+    }
+
+    let param = |i: usize| params.get(i).cloned().unwrap_or(Value::Null);
+    match method {
+        "aria2.addUri" => add_uri(&param(0), &param(1)),
+        "aria2.remove" | "aria2.forceRemove" => control(&param(0), MainFormMessage::RemoveTaskById),
+        "aria2.pause" | "aria2.forcePause" => control(&param(0), MainFormMessage::StopTaskById),
+        "aria2.unpause" => control(&param(0), MainFormMessage::StartTaskById),
+        "aria2.tellStatus" => find_task(&param(0)).map(|x| to_status(&x, &param(1))),
+        "aria2.tellActive" => Ok(tell(|x| x == "active", &param(0))),
+        "aria2.tellWaiting" => Ok(page(
+            tell(|x| x == "waiting" || x == "paused", &param(2)),
+            &param(0),
+            &param(1),
+        )),
+        "aria2.tellStopped" => Ok(page(
+            tell(|x| x == "complete" || x == "error", &param(2)),
+            &param(0),
+            &param(1),
+        )),
+        "aria2.getGlobalStat" => Ok(get_global_stat()),
+        "aria2.getVersion" => Ok(json!({ "version": ARIA2_VERSION, "enabledFeatures": [] })),
+        "system.multicall" => multicall(&param(0), token),
+        "system.listMethods" => Ok(json!(METHODS)),
+        _ => Err((1, format!("No such method: {}", method))),
+    }
+}
+
+fn multicall(calls: &Value, token: &str) -> RpcResult {
+    let calls = calls
+        .as_array()
+        .ok_or((1, "Calls should be an array".to_owned()))?;
+    let results = calls
+        .iter()
+        .map(|call| {
+            let method = call["methodName"].as_str().unwrap_or("");
+            let params = call["params"].as_array().cloned().unwrap_or_default();
+            match dispatch(method, params, token) {
+                Ok(result) => json!([result]),
+                Err((code, message)) => json!({ "code": code, "message": message }),
+            }
+        })
+        .collect();
+    Ok(Value::Array(results))
+}
+
+/// Queues the first uri as a direct task, and starts it like aria2 does.
+///
+/// Options `dir` and `out` become save option, a `Cookie` header becomes cookies,
+/// and other headers, `referer` and `user-agent` are sent with the request.
+fn add_uri(uris: &Value, options: &Value) -> RpcResult {
+    let url = uris
+        .as_array()
+        .and_then(|x| x.first())
+        .and_then(|x| x.as_str())
+        .ok_or((1, "No uri is given".to_owned()))?;
+    let url = url::Url::parse(url).map_err(|x| (1, x.to_string()))?;
+
+    let out = options["out"]
+        .as_str()
+        .map(|x| x.to_owned())
+        .or_else(|| {
+            url.path_segments()
+                .and_then(|x| x.last())
+                .filter(|x| x.len() > 0)
+                .map(|x| x.to_owned())
+        })
+        .unwrap_or("download".to_owned());
+    // `out` is a file name inside `dir`, paths could write anywhere else
+    if out.contains(['/', '\\']) || out == "." || out == ".." {
+        return Err((1, format!("Invalid out: {}", out)));
+    }
+    let (title, ext) = match out.rsplit_once('.') {
+        Some((stem, ext)) if stem.len() > 0 => (stem.to_owned(), ext.to_owned()),
+        _ => (out.clone(), String::new()),
+    };
+
+    let config = get_config();
+    let output_dir = options["dir"]
+        .as_str()
+        .map(|x| x.to_owned())
+        .or(config.output_dir)
+        .or_else(|| {
+            directories::UserDirs::new()
+                .and_then(|x| x.download_dir().map(|p| p.to_string_lossy().to_string()))
+        })
+        .ok_or((1, "No output dir".to_owned()))?;
+    let output = Path::new(&output_dir)
+        .join(&out)
+        .to_string_lossy()
+        .to_string();
+
+    // `header` is a single string or an array of `Name: value`
+    let header_lines: Vec<String> = match &options["header"] {
+        Value::String(header) => vec![header.clone()],
+        Value::Array(headers) => headers
+            .iter()
+            .filter_map(|x| x.as_str().map(|x| x.to_owned()))
+            .collect(),
+        _ => Vec::new(),
+    };
+    let mut cookies = None;
+    let mut headers = Vec::new();
+    for header in header_lines {
+        match header.split_once(':') {
+            Some((name, value)) if name.trim().eq_ignore_ascii_case("cookie") => {
+                cookies = Some(value.trim().to_owned())
+            }
+            Some(_) => headers.push(header),
+            None => {}
+        }
+    }
+    if let Some(referer) = options["referer"].as_str() {
+        headers.push(format!("Referer: {}", referer));
+    }
+    if let Some(user_agent) = options["user-agent"].as_str() {
+        headers.push(format!("User-Agent: {}", user_agent));
+    }
+
+    let info = DownloadInfo {
+        url: url.to_string(),
+        site: url.host_str().unwrap_or_default().to_owned(),
+        title: title.clone(),
+        ext: ext.clone(),
+        stream_id: DIRECT_ENGINE.to_owned(),
+        stream_name: DIRECT_ENGINE.to_owned(),
+        downloader: DIRECT_ENGINE.to_owned(),
+        save_option: Some(SaveOption {
+            output_dir,
+            file_name: title,
+        }),
+        cookies,
+        headers,
+        parts: vec![PartInfo {
+            url: url.to_string(),
+            size: 0,
+            ext,
+        }],
+        ..Default::default()
+    };
+
+    // Clients ask for status right after adding, before task table gets the task
+    let uuid = Uuid::new_v4();
+    add_pending_task(TaskSnapshot {
+        id: uuid.to_string(),
+        title: info.title.clone(),
+        url: info.url.clone(),
+        engine: info.downloader.clone(),
+        stream: info.stream_name.clone(),
+        ext: info.ext.clone(),
+        size: 0,
+        progress: 0.0,
+        bytes: 0,
+        speed: 0,
+        eta: 0,
+        status: "Queued".to_owned(),
+        output,
+    });
+    send_message(MainFormMessage::AddTaskWithId(uuid, Arc::new(info)));
+    Ok(json!(get_gid(&uuid.to_string())))
+}
+
+fn control(gid: &Value, message: fn(Uuid) -> MainFormMessage) -> RpcResult {
+    let task = find_task(gid)?;
+    let uuid = Uuid::parse_str(&task.id).map_err(|x| (1, x.to_string()))?;
+    send_message(message(uuid));
+    Ok(json!(get_gid(&task.id)))
+}
+
+/// aria2 gids are 16 hex digits, taken from task uuid.
+fn get_gid(id: &str) -> String {
+    id.replace('-', "").chars().take(16).collect()
+}
+
+fn find_task(gid: &Value) -> std::result::Result<TaskSnapshot, (i64, String)> {
+    let gid = gid.as_str().unwrap_or_default();
+    get_tasks()
+        .into_iter()
+        .find(|x| get_gid(&x.id) == gid)
+        .ok_or((1, format!("GID {} is not found", gid)))
+}
+
+fn get_aria2_status(task: &TaskSnapshot) -> &'static str {
+    match task.status.as_str() {
+        "Running" => "active",
+        "Queued" | "Waiting" => "waiting",
+        "Finished" => "complete",
+        "Failed" => "error",
+        _ => "paused",
+    }
+}
+
+fn tell(filter: impl Fn(&str) -> bool, keys: &Value) -> Value {
+    let tasks = get_tasks()
+        .iter()
+        .filter(|x| filter(get_aria2_status(x)))
+        .map(|x| to_status(x, keys))
+        .collect();
+    Value::Array(tasks)
+}
+
+fn page(tasks: Value, offset: &Value, num: &Value) -> Value {
+    let tasks = tasks.as_array().cloned().unwrap_or_default();
+    let offset = offset.as_u64().unwrap_or(0) as usize;
+    let num = num.as_u64().unwrap_or(tasks.len() as u64) as usize;
+    Value::Array(tasks.into_iter().skip(offset).take(num).collect())
+}
+
+/// Builds aria2 status of a task, keeping only `keys` if any is given.
+fn to_status(task: &TaskSnapshot, keys: &Value) -> Value {
+    let total = task.size as u64;
+    let completed = match task.bytes {
+        0 => (task.progress * total as f64) as u64,
+        bytes => bytes,
+    };
+    let dir = Path::new(&task.output)
+        .parent()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let status = json!({
+        "gid": get_gid(&task.id),
+        "status": get_aria2_status(task),
+        "totalLength": total.to_string(),
+        "completedLength": completed.to_string(),
+        "downloadSpeed": task.speed.to_string(),
+        "uploadLength": "0",
+        "uploadSpeed": "0",
+        "connections": "1",
+        "dir": dir,
+        "files": [{
+            "index": "1",
+            "path": task.output,
+            "length": total.to_string(),
+            "completedLength": completed.to_string(),
+            "selected": "true",
+            "uris": [{ "uri": task.url, "status": "used" }],
+        }],
+    });
+
+    match keys.as_array() {
+        Some(keys) if keys.len() > 0 => {
+            let status = status.as_object().cloned().unwrap_or_default();
+            let status = status
+                .into_iter()
+                .filter(|(key, _)| keys.iter().any(|x| x.as_str() == Some(key.as_str())))
+                .collect();
+            Value::Object(status)
+        }
+        _ => status,
+    }
+}
+
+fn get_global_stat() -> Value {
+    let tasks = get_tasks();
+    let count = |status: &[&str]| {
+        tasks
+            .iter()
+            .filter(|x| status.contains(&get_aria2_status(x)))
+            .count()
+            .to_string()
+    };
+    let speed: usize = tasks.iter().map(|x| x.speed).sum();
+    json!({
+        "downloadSpeed": speed.to_string(),
+        "uploadSpeed": "0",
+        "numActive": count(&["active"]),
+        "numWaiting": count(&["waiting", "paused"]),
+        "numStopped": count(&["complete", "error"]),
+        "numStoppedTotal": count(&["complete", "error"]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gid_and_page() {
+        assert_eq!(
+            "0123456789abcdef",
+            get_gid("01234567-89ab-cdef-0123-456789abcdef")
+        );
+        assert_eq!(
+            json!([2, 3]),
+            page(json!([1, 2, 3, 4]), &json!(1), &json!(2))
+        );
+        assert_eq!(
+            json!([3, 4]),
+            page(json!([1, 2, 3, 4]), &json!(2), &Value::Null)
+        );
+    }
+
+    #[test]
+    fn test_add_uri_out() {
+        let uris = json!(["https://example.com/a.zip"]);
+        for out in ["../a.zip", "/tmp/a.zip", "a\\..\\b.zip", ".."] {
+            assert!(add_uri(&uris, &json!({ "out": out })).is_err());
+        }
+    }
+}
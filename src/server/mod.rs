@@ -0,0 +1,333 @@
+use std::{
+    io::{Read, Write},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+use uuid::Uuid;
+
+use crate::{
+    archive::is_archived,
+    config::{get_config, set_config},
+    downloader::*,
+    send_message,
+    view::MainFormMessage,
+};
+
+mod jsonrpc;
+
+lazy_static::lazy_static! {
+    static ref TASKS: RwLock<Vec<TaskSnapshot>> = RwLock::new(Vec::new());
+    /// Tasks added through the API which task table hasn't published yet.
+    static ref PENDING: RwLock<Vec<TaskSnapshot>> = RwLock::new(Vec::new());
+}
+
+/// Task state visible to API clients, published when task table refreshes.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TaskSnapshot {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    pub engine: String,
+    pub stream: String,
+    pub ext: String,
+    pub size: usize,
+    pub progress: f64,
+    pub bytes: u64,
+    pub speed: usize,
+    pub eta: usize,
+    pub status: String,
+    /// Output file path, which engines may still change.
+    pub output: String,
+}
+
+pub fn publish_tasks(tasks: Vec<TaskSnapshot>) {
+    let mut published = TASKS.write().unwrap();
+    PENDING
+        .write()
+        .unwrap()
+        .retain(|x| tasks.iter().all(|y| y.id != x.id));
+    *published = tasks;
+}
+
+/// Makes a task visible to API clients right away, before task table adds it.
+fn add_pending_task(task: TaskSnapshot) {
+    PENDING.write().unwrap().push(task);
+}
+
+fn get_tasks() -> Vec<TaskSnapshot> {
+    let published = TASKS.read().unwrap();
+    let mut tasks = published.clone();
+    tasks.extend(PENDING.read().unwrap().iter().cloned());
+    tasks
+}
+
+#[derive(Deserialize)]
+struct AddRequest {
+    url: String,
+    engine: Option<String>,
+    preference: Option<String>,
+    output_dir: Option<String>,
+}
+
+/// Status code and message of a failed request.
+type ApiResult = std::result::Result<Value, (u16, String)>;
+
+/// Starts the API on `127.0.0.1:<api_port>` in background, a token is generated
+/// and saved on first start.
+pub fn start() -> Result<()> {
+    let mut config = get_config();
+    let token = match config.api_token.clone() {
+        Some(token) => token,
+        None => {
+            let token = Uuid::new_v4().simple().to_string();
+            config.api_token = Some(token.clone());
+            set_config(config.clone())?;
+            token
+        }
+    };
+
+    let server = Server::http(("127.0.0.1", config.api_port))
+        .map_err(|x| anyhow!("Failed to listen on port {}: {}", config.api_port, x))?;
+    let token = Arc::new(token);
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let token = token.clone();
+            // Adding urls waits for detecting, and event streams never end
+            std::thread::spawn(move || handle_request(request, &token));
+        }
+    });
+
+    Ok(())
+}
+
+fn handle_request(mut request: Request, token: &str) {
+    // Preflight of browser scripts
+    if *request.method() == Method::Options {
+        let response = Response::empty(204)
+            .with_header(header("Access-Control-Allow-Origin", "*"))
+            .with_header(header("Access-Control-Allow-Methods", "GET, POST, DELETE"))
+            .with_header(header(
+                "Access-Control-Allow-Headers",
+                "Authorization, Content-Type",
+            ));
+        let _ = request.respond(response);
+        return;
+    }
+
+    let path = request.url().split('?').next().unwrap_or("").to_owned();
+    let segments: Vec<&str> = path.split('/').filter(|x| x.len() > 0).collect();
+    let method = request.method().clone();
+
+    // aria2 clients send the token as `token:<secret>` in params instead
+    if method == Method::Post && segments == ["jsonrpc"] {
+        let response = jsonrpc::handle_jsonrpc(&mut request, token);
+        let _ = request.respond(response);
+        return;
+    }
+
+    if is_authorized(&request, token) == false {
+        let _ = request.respond(json_response(401, &json!({ "error": "Invalid token" })));
+        return;
+    }
+
+    if method == Method::Get && segments == ["events"] {
+        stream_events(request);
+        return;
+    }
+
+    let result = match (method, segments.as_slice()) {
+        (Method::Get, ["tasks"]) => Ok(json!(get_tasks())),
+        (Method::Post, ["tasks"]) => add_url(&mut request),
+        (Method::Post, ["tasks", id, "start"]) => control_task(id, MainFormMessage::StartTaskById),
+        (Method::Post, ["tasks", id, "stop"]) => control_task(id, MainFormMessage::StopTaskById),
+        (Method::Delete, ["tasks", id]) => control_task(id, MainFormMessage::RemoveTaskById),
+        _ => Err((404, "No such api".to_owned())),
+    };
+
+    let response = match result {
+        Ok(value) => json_response(200, &value),
+        Err((status, error)) => json_response(status, &json!({ "error": error })),
+    };
+    let _ = request.respond(response);
+}
+
+/// Accepts `Authorization: Bearer <token>`, or `?token=<token>` as `EventSource`
+/// of browsers can't set headers.
+fn is_authorized(request: &Request, token: &str) -> bool {
+    let bearer = format!("Bearer {}", token);
+    let by_header = request
+        .headers()
+        .iter()
+        .any(|x| x.field.equiv("Authorization") && x.value.as_str() == bearer);
+    let by_query = url::Url::parse(&format!("http://localhost{}", request.url()))
+        .map(|x| {
+            x.query_pairs()
+                .any(|(key, value)| key == "token" && value == token)
+        })
+        .unwrap_or(false);
+    by_header || by_query
+}
+
+/// Detects the url and queues preferred streams, with the same rule lookup as
+/// batch dialog.
+fn add_url(request: &mut Request) -> ApiResult {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .read_to_string(&mut body)
+        .map_err(|x| (400, x.to_string()))?;
+    let add: AddRequest = serde_json::from_str(&body).map_err(|x| (400, x.to_string()))?;
+
+    let config = get_config();
+    let rule = get_rule(&add.url).unwrap_or_default();
+    let engine = add
+        .engine
+        .or(rule.engine.clone())
+        .unwrap_or(AUTO_ENGINE.to_owned());
+    let preference = add
+        .preference
+        .or(rule.stream.clone())
+        .or(config.stream_preference)
+        .unwrap_or("best".to_owned());
+    let preference = Preference::parse(&preference).map_err(|x| (400, x.to_string()))?;
+    let output_dir = add
+        .output_dir
+        .or(config.output_dir)
+        .or_else(|| {
+            directories::UserDirs::new()
+                .and_then(|x| x.download_dir().map(|p| p.to_string_lossy().to_string()))
+        })
+        .ok_or((400, "No output dir".to_owned()))?;
+
+    let cookie_file = rule
+        .cookie_file
+        .as_ref()
+        .and_then(|x| std::fs::read_to_string(x).ok())
+        .and_then(|x| store_cookies(&x).ok());
+    let info_map = get_stream_info(&engine, &add.url, cookie_file.as_deref());
+    if let Some(cookie_file) = cookie_file {
+        let _ = std::fs::remove_file(cookie_file);
+    }
+    let info_map = info_map.map_err(|x| (422, x.to_string()))?;
+
+    let tasks = select_tasks(&info_map, &preference, &rule, &output_dir);
+    if tasks.len() == 0 {
+        return Err((422, "No stream matches preference".to_owned()));
+    }
+    if config.skip_archived && tasks.iter().any(|x| is_archived(x)) {
+        return Err((409, "Already in download archive".to_owned()));
+    }
+
+    // Ids are returned to client, which controls the tasks with them right away
+    let tasks: Vec<(Uuid, DownloadInfo)> = tasks.into_iter().map(|x| (Uuid::new_v4(), x)).collect();
+    for (uuid, info) in &tasks {
+        add_pending_task(TaskSnapshot {
+            id: uuid.to_string(),
+            title: info.title.clone(),
+            url: info.url.clone(),
+            engine: info.downloader.clone(),
+            stream: info.stream_name.clone(),
+            ext: info.ext.clone(),
+            size: info.stream_size,
+            progress: 0.0,
+            bytes: 0,
+            speed: 0,
+            eta: 0,
+            status: match info.schedule {
+                Some(_) => "Waiting".to_owned(),
+                None => "Queued".to_owned(),
+            },
+            output: info.get_output(),
+        });
+    }
+    let result = json!({
+        "title": tasks[0].1.title,
+        "ids": tasks.iter().map(|(x, _)| x.to_string()).collect::<Vec<String>>(),
+        "streams": tasks.iter().map(|(_, x)| x.stream_name.clone()).collect::<Vec<String>>(),
+    });
+    send_message(MainFormMessage::AddTasksWithIds(Arc::new(tasks)));
+    Ok(result)
+}
+
+fn control_task(id: &str, message: fn(Uuid) -> MainFormMessage) -> ApiResult {
+    let uuid = Uuid::parse_str(id).map_err(|x| (400, x.to_string()))?;
+    if get_tasks().iter().all(|x| x.id != uuid.to_string()) {
+        return Err((404, "No such task".to_owned()));
+    }
+    send_message(message(uuid));
+    Ok(json!({ "id": uuid.to_string() }))
+}
+
+/// Writes server-sent events of changed and removed tasks until client leaves.
+fn stream_events(request: Request) {
+    let mut writer = request.into_writer();
+    let head = "HTTP/1.1 200 OK\r\n\
+                Content-Type: text/event-stream\r\n\
+                Cache-Control: no-cache\r\n\
+                Access-Control-Allow-Origin: *\r\n\r\n";
+    if writer
+        .write_all(head.as_bytes())
+        .and_then(|_| writer.flush())
+        .is_err()
+    {
+        return;
+    }
+
+    let mut last: Vec<TaskSnapshot> = Vec::new();
+    let mut idle = 0;
+    loop {
+        let tasks = get_tasks();
+        let mut events = String::new();
+        for task in tasks.iter().filter(|x| last.contains(x) == false) {
+            events.push_str(&format_event("task", task));
+        }
+        for task in last.iter().filter(|x| tasks.iter().all(|y| y.id != x.id)) {
+            events.push_str(&format_event("removed", &json!({ "id": task.id })));
+        }
+
+        // Comments keep idle connections alive and find out closed ones
+        idle = match events.len() {
+            0 => idle + 1,
+            _ => 0,
+        };
+        if idle >= 15 {
+            events.push_str(": ping\n\n");
+            idle = 0;
+        }
+
+        if events.len() > 0
+            && writer
+                .write_all(events.as_bytes())
+                .and_then(|_| writer.flush())
+                .is_err()
+        {
+            break;
+        }
+        last = tasks;
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
+
+fn format_event<T: Serialize>(event: &str, data: &T) -> String {
+    format!(
+        "event: {}\ndata: {}\n\n",
+        event,
+        serde_json::to_string(data).unwrap_or_default()
+    )
+}
+
+fn json_response(status: u16, value: &Value) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_string(value.to_string())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+        .with_header(header("Access-Control-Allow-Origin", "*"))
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field.as_bytes(), value.as_bytes()).unwrap()
+}
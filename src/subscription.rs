@@ -0,0 +1,324 @@
+use std::{collections::HashSet, io::Read, path::PathBuf, sync::Mutex};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    archive::{add_to_archive, get_url_key, is_archived, is_entry_archived},
+    config::{get_app_dir, get_config, DomainRule},
+    downloader::*,
+    history::{append_history, HistoryEntry, Outcome},
+};
+
+lazy_static::lazy_static! {
+    /// Keys of entries queued in this session, which are not archived until finished.
+    static ref QUEUED: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// A channel or playlist whose new videos are queued automatically.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Subscription {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    /// Unset fields fall back to the site rule of `url`, then to options.
+    pub engine: Option<String>,
+    pub preference: Option<String>,
+    pub output_dir: Option<String>,
+    pub file_name: Option<String>,
+    pub enabled: bool,
+    /// Queues the videos already there on first check, instead of only later ones.
+    pub download_existing: bool,
+    /// Keys of the videos skipped on first check.
+    pub baseline: Vec<String>,
+    /// Unix timestamp in seconds.
+    pub last_checked: Option<i64>,
+    pub last_status: String,
+}
+
+impl Default for Subscription {
+    fn default() -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            name: String::new(),
+            url: String::new(),
+            engine: None,
+            preference: None,
+            output_dir: None,
+            file_name: None,
+            enabled: true,
+            download_existing: false,
+            baseline: Vec::new(),
+            last_checked: None,
+            last_status: String::new(),
+        }
+    }
+}
+
+/// New videos found by a check, already detected and ready to queue.
+#[derive(Clone, Debug, Default)]
+pub struct CheckResult {
+    pub tasks: Vec<DownloadInfo>,
+    /// Set on first check, when existing videos are skipped.
+    pub baseline: Option<Vec<String>>,
+    pub new: usize,
+    pub failed: usize,
+}
+
+impl Subscription {
+    /// Merges own settings over the site rule of the url.
+    pub fn get_rule(&self) -> DomainRule {
+        let rule = get_rule(&self.url).unwrap_or_default();
+        DomainRule {
+            pattern: rule.pattern,
+            engine: self.engine.clone().or(rule.engine),
+            stream: self.preference.clone().or(rule.stream),
+            cookie_file: rule.cookie_file,
+            output_dir: self.output_dir.clone().or(rule.output_dir),
+            file_name: self.file_name.clone().or(rule.file_name),
+        }
+    }
+
+    /// Checks if `interval` minutes passed since last check.
+    pub fn is_due(&self, now: i64, interval: u64) -> bool {
+        self.enabled
+            && self
+                .last_checked
+                .map(|x| now - x >= interval as i64 * 60)
+                .unwrap_or(true)
+    }
+
+    /// Records the outcome of a check.
+    pub fn apply(&mut self, result: &Result<CheckResult, String>) {
+        self.last_checked = Some(chrono::Local::now().timestamp());
+        self.last_status = match result {
+            Ok(result) => {
+                if let Some(baseline) = result.baseline.as_ref() {
+                    self.baseline = baseline.clone();
+                }
+                match (result.baseline.as_ref(), result.new, result.failed) {
+                    (Some(baseline), ..) => {
+                        format!("Subscribed, {} existing skipped", baseline.len())
+                    }
+                    (_, 0, 0) => "Up to date".to_owned(),
+                    (_, new, 0) => format!("{} new", new),
+                    (_, new, failed) => format!("{} new, {} failed", new, failed),
+                }
+            }
+            Err(error) => format!("Failed: {}", error),
+        };
+    }
+}
+
+fn get_entry_key(entry: &PlaylistEntry) -> String {
+    entry
+        .archive_id
+        .clone()
+        .unwrap_or_else(|| get_url_key(&entry.url))
+}
+
+/// Lists the subscription, and detects videos neither archived, skipped on
+/// first check nor queued before.
+pub fn check_subscription(subscription: &Subscription) -> Result<CheckResult> {
+    let config = get_config();
+    let rule = subscription.get_rule();
+    let engine = rule.engine.clone().unwrap_or(AUTO_ENGINE.to_owned());
+    let preference = rule
+        .stream
+        .clone()
+        .or(config.stream_preference)
+        .unwrap_or("best".to_owned());
+    let preference = Preference::parse(&preference)?;
+    let output_dir = config
+        .output_dir
+        .or_else(|| {
+            directories::UserDirs::new()
+                .and_then(|x| x.download_dir().map(|p| p.to_string_lossy().to_string()))
+        })
+        .unwrap_or("./".to_owned());
+
+    let cookie_file = rule
+        .cookie_file
+        .as_ref()
+        .and_then(|x| std::fs::read_to_string(x).ok())
+        .and_then(|x| store_cookies(&x).ok());
+    let result = check_entries(
+        subscription,
+        &engine,
+        &preference,
+        &rule,
+        &output_dir,
+        cookie_file.as_deref(),
+    );
+    if let Some(cookie_file) = cookie_file {
+        let _ = std::fs::remove_file(cookie_file);
+    }
+    result
+}
+
+fn check_entries(
+    subscription: &Subscription,
+    engine: &str,
+    preference: &Preference,
+    rule: &DomainRule,
+    output_dir: &str,
+    cookie_file: Option<&std::path::Path>,
+) -> Result<CheckResult> {
+    let entries = list_entries(engine, &subscription.url, cookie_file)?;
+    if subscription.last_checked.is_none() && subscription.download_existing == false {
+        return Ok(CheckResult {
+            baseline: Some(entries.iter().map(get_entry_key).collect()),
+            ..Default::default()
+        });
+    }
+
+    let mut result = CheckResult::default();
+    for entry in entries {
+        let key = get_entry_key(&entry);
+        if subscription.baseline.contains(&key)
+            || QUEUED.lock().unwrap().contains(&key)
+            || is_entry_archived(&entry)
+        {
+            continue;
+        }
+
+        result.new = result.new + 1;
+        let tasks = get_stream_info(engine, &entry.url, cookie_file)
+            .map(|x| select_tasks(&x, preference, rule, output_dir))
+            .unwrap_or_default();
+        if tasks.len() == 0 {
+            result.failed = result.failed + 1;
+            continue;
+        }
+
+        // Archived under the listed key, so that next listing matches it
+        result.tasks.extend(tasks.into_iter().map(|mut x| {
+            x.archive_id = Some(key.clone());
+            x
+        }));
+        QUEUED.lock().unwrap().insert(key);
+    }
+
+    Ok(result)
+}
+
+fn get_subscriptions_file() -> Result<PathBuf> {
+    Ok(get_app_dir()?.join("subscriptions.json"))
+}
+
+pub fn load_subscriptions() -> Vec<Subscription> {
+    get_subscriptions_file()
+        .and_then(|x| Ok(std::fs::read_to_string(x)?))
+        .and_then(|x| Ok(serde_json::from_str(&x)?))
+        .unwrap_or_default()
+}
+
+pub fn save_subscriptions(subscriptions: &[Subscription]) -> Result<()> {
+    let content = serde_json::to_string_pretty(subscriptions)?;
+    std::fs::write(get_subscriptions_file()?, content)?;
+    Ok(())
+}
+
+/// Runs the engine until it exits, recording history and archive as task table does.
+fn download_headless(info: &DownloadInfo) -> Result<()> {
+    let started_at = chrono::Local::now().timestamp();
+    let result = (|| -> Result<()> {
+        let (mut child, cookie_file, engine) = execute_download_info(info)?;
+        // Engines block when their output pipe is full
+        let mut output: Box<dyn Read> = match engine.is_stderr_output() {
+            true => Box::new(child.stderr.take().unwrap()),
+            false => Box::new(child.stdout.take().unwrap()),
+        };
+        let _ = std::io::copy(&mut output, &mut std::io::sink());
+        let status = child.wait();
+        if let Some(cookie_file) = cookie_file {
+            let _ = std::fs::remove_file(cookie_file);
+        }
+        match status? {
+            status if status.success() => Ok(()),
+            status => Err(anyhow::anyhow!(
+                "{} exited with {}",
+                info.downloader,
+                status
+            )),
+        }
+    })();
+
+    let (outcome, error) = match &result {
+        Ok(_) => (Outcome::Finished, None),
+        Err(error) => (Outcome::Failed, Some(error.to_string())),
+    };
+    append_history(&HistoryEntry::new(info, started_at, outcome, error))?;
+    if result.is_ok() {
+        add_to_archive(info)?;
+    }
+    result
+}
+
+/// Checks every enabled subscription and downloads new videos one by one, for
+/// `ugdown --poll-subscriptions` run by cron or task scheduler.
+pub fn poll_headless() -> Result<()> {
+    let mut subscriptions = load_subscriptions();
+    for i in 0..subscriptions.len() {
+        if subscriptions[i].enabled == false {
+            continue;
+        }
+
+        println!(
+            "Checking {} ({})",
+            subscriptions[i].name, subscriptions[i].url
+        );
+        let result = check_subscription(&subscriptions[i]).map_err(|x| x.to_string());
+        subscriptions[i].apply(&result);
+        save_subscriptions(&subscriptions)?;
+        println!("  {}", subscriptions[i].last_status);
+
+        for info in result.map(|x| x.tasks).unwrap_or_default() {
+            if is_archived(&info) {
+                continue;
+            }
+            println!("  Downloading {} [{}]", info.title, info.stream_name);
+            if let Err(error) = download_headless(&info) {
+                println!("  Failed: {}", error);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscription_status() {
+        let mut subscription = Subscription::default();
+        assert!(subscription.is_due(0, 60));
+
+        subscription.apply(&Ok(CheckResult {
+            baseline: Some(vec!["youtube aaa".to_owned(), "youtube bbb".to_owned()]),
+            ..Default::default()
+        }));
+        assert_eq!("Subscribed, 2 existing skipped", subscription.last_status);
+        assert_eq!(2, subscription.baseline.len());
+
+        let checked_at = subscription.last_checked.unwrap();
+        assert!(subscription.is_due(checked_at + 59 * 60, 60) == false);
+        assert!(subscription.is_due(checked_at + 60 * 60, 60));
+
+        subscription.apply(&Ok(CheckResult {
+            new: 3,
+            failed: 1,
+            ..Default::default()
+        }));
+        assert_eq!("3 new, 1 failed", subscription.last_status);
+        assert_eq!(2, subscription.baseline.len());
+
+        subscription.apply(&Err("network".to_owned()));
+        assert_eq!("Failed: network", subscription.last_status);
+
+        subscription.enabled = false;
+        assert!(subscription.is_due(checked_at + 120 * 60, 60) == false);
+    }
+}
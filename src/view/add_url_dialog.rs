@@ -1,11 +1,16 @@
 use std::{collections::HashMap, sync::Arc};
 
-use crate::{downloader::*, send_message, AppMessage};
+use crate::{
+    archive::{is_archived, is_url_archived},
+    config::{get_config, DomainRule},
+    downloader::*,
+    send_message, AppMessage,
+};
 use fltk::{prelude::*, *};
 
 use anyhow::{anyhow, Result};
 
-use super::{utils::size_to_string, MainFormMessage, StatusBar};
+use super::{BatchDialogMessage, MainFormMessage, StatusBar, StreamFilter, StreamTable};
 
 mod add_url_dialog {
     fl2rust_macro::include_ui!("./src/ui/add_url.fl");
@@ -14,32 +19,48 @@ mod add_url_dialog {
 #[derive(Clone)]
 pub struct AddUrlDialog {
     add_url_dialog: add_url_dialog::UserInterface,
-    current_idx: HashMap<i32, DownloadInfo>,
+    stream_table: StreamTable,
+    /// Cookies set by hand, which win over the ones of site rule.
     current_cookies: Option<String>,
+    /// Cookies read from cookie file of `current_rule`.
+    rule_cookies: Option<String>,
+    current_rule: Option<DomainRule>,
 }
 
 impl AddUrlDialog {
     pub fn default() -> Self {
         let mut add_url_dialog = add_url_dialog::UserInterface::make_window();
 
-        if let Some(download_dir) = directories::UserDirs::new()
-            .and_then(|x| x.download_dir().map(|p| p.to_string_lossy().to_string()))
-        {
+        if let Some(download_dir) = get_default_dir() {
             add_url_dialog.input_dir.set_value(&download_dir);
         }
 
-        let current_idx: HashMap<i32, DownloadInfo> = Default::default();
+        let stream_table = StreamTable::default();
+        add_url_dialog
+            .stream_table_parent
+            .add_resizable(&**stream_table);
+        let stream_table = stream_table.size_of_parent().center_of_parent();
+
         let current_cookies: Option<String> = Default::default();
 
+        let mut engine_names = vec![AUTO_ENGINE.to_owned()];
+        engine_names.extend(get_engine_names());
         add_url_dialog
             .choice_engine
-            .add_choice(get_engine_names().join("|").as_str());
+            .add_choice(engine_names.join("|").as_str());
         add_url_dialog.choice_engine.set_value(0);
 
+        add_url_dialog
+            .choice_stream_filter
+            .add_choice(StreamFilter::NAMES.join("|").as_str());
+        add_url_dialog.choice_stream_filter.set_value(0);
+
         let mut result = Self {
             add_url_dialog,
-            current_idx,
+            stream_table,
             current_cookies,
+            rule_cookies: None,
+            current_rule: None,
         };
 
         result.bind_message();
@@ -48,6 +69,12 @@ impl AddUrlDialog {
     }
 
     fn bind_message(&mut self) {
+        self.add_url_dialog
+            .input_url
+            .set_trigger(enums::CallbackTrigger::Changed);
+        self.add_url_dialog
+            .input_url
+            .set_callback(|_| send_message(AddUrlDialogMessage::UrlChanged));
         self.add_url_dialog
             .btn_detect
             .set_callback(|_| send_message(AddUrlDialogMessage::Detect));
@@ -74,6 +101,10 @@ impl AddUrlDialog {
         self.add_url_dialog
             .btn_set_cookie
             .set_callback(|_| send_message(AddUrlDialogMessage::SetCookies));
+
+        self.add_url_dialog
+            .choice_stream_filter
+            .set_callback(|_| send_message(AddUrlDialogMessage::FilterStreams));
     }
 
     fn detect(&mut self) -> Result<()> {
@@ -82,6 +113,15 @@ impl AddUrlDialog {
             return Err(anyhow!("Url is empty!"));
         }
 
+        // Several urls are detected in batch dialog
+        let urls = extract_urls(&url);
+        if urls.len() > 1 {
+            send_message(BatchDialogMessage::Import(urls.join("\n")));
+            self.reset();
+            self.add_url_dialog.window.hide();
+            return Ok(());
+        }
+
         let engine = self
             .add_url_dialog
             .choice_engine
@@ -89,7 +129,11 @@ impl AddUrlDialog {
             .ok_or_else(|| anyhow!("Please select engine"))?;
 
         self.add_url_dialog.btn_detect.deactivate();
-        let current_cookies = self.current_cookies.clone();
+        if is_url_archived(&url) {
+            self.add_url_dialog
+                .set_status_bar_message("Already in download archive, detecting anyway...");
+        }
+        let current_cookies = self.get_cookies();
         let mut add_url_dialog = self.add_url_dialog.clone();
         std::thread::spawn(move || {
             let cookie_file = current_cookies.and_then(|x| store_cookies(&x).ok());
@@ -110,38 +154,125 @@ impl AddUrlDialog {
     }
 
     fn update_with_stream_info(&mut self, stream_info: &HashMap<String, DownloadInfo>) {
-        self.current_idx.clear();
-
         let mut title_updated = false;
-        self.add_url_dialog.checkbrowser.clear();
 
+        let mut engines: Vec<String> = Vec::new();
         for (_id, info) in stream_info {
             if title_updated == false {
                 self.add_url_dialog.output_title.set_value(&info.title);
                 title_updated = true;
             }
-
-            let check_item = format!(
-                "{} - {} (size: {})",
-                info.ext,
-                info.stream_name,
-                size_to_string(info.stream_size)
-            );
-            let idx = self
-                .add_url_dialog
-                .checkbrowser
-                .add(check_item.as_str(), false);
-
-            self.current_idx.insert(idx, info.clone());
-
-            self.add_url_dialog.checkbrowser.redraw();
+            if engines.contains(&info.downloader) == false {
+                engines.push(info.downloader.clone());
+            }
         }
 
+        self.stream_table
+            .set_streams(stream_info.values().cloned().collect());
+
         if self.add_url_dialog.btn_detect.active() == false {
             self.add_url_dialog.btn_detect.activate()
         }
+
+        if let Err(error) = self.select_preferred() {
+            self.add_url_dialog.set_status_bar_error(&error.to_string());
+            return;
+        }
+        if stream_info.values().any(|x| is_archived(x)) {
+            self.add_url_dialog
+                .set_status_bar_error("Already downloaded before, see download archive");
+            return;
+        }
         self.add_url_dialog
-            .set_status_bar_success("Detected successfully!");
+            .set_status_bar_success(&format!("Detected by {} successfully!", engines.join(", ")));
+    }
+
+    /// Checks the streams selected by preference of site rule, or the default one.
+    fn select_preferred(&mut self) -> Result<()> {
+        let expr = match self
+            .current_rule
+            .as_ref()
+            .and_then(|x| x.stream.clone())
+            .or(get_config().stream_preference)
+        {
+            Some(expr) => expr,
+            None => return Ok(()),
+        };
+        let preference = Preference::parse(&expr)?;
+
+        let streams = self.stream_table.get_streams();
+        let infos: Vec<&DownloadInfo> = streams.iter().collect();
+
+        for i in preference.select(&infos) {
+            self.stream_table.set_checked(i, true);
+        }
+
+        Ok(())
+    }
+
+    /// Shows dialog pre-filled with `url` and starts detecting.
+    fn show_url(&mut self, url: &str) {
+        self.add_url_dialog.input_url.set_value(url);
+        self.apply_rule();
+        self.add_url_dialog.window.show();
+        if let Err(error) = self.detect() {
+            self.add_url_dialog.set_status_bar_error(&error.to_string());
+        }
+    }
+
+    /// Applies the site rule matching current url, if any.
+    fn apply_rule(&mut self) {
+        let url = self.add_url_dialog.input_url.value();
+        let rule = get_rule(url.trim());
+        if rule == self.current_rule {
+            return;
+        }
+        self.clear_rule();
+
+        if let Some(rule) = rule.as_ref() {
+            if let Some(engine) = rule.engine.as_ref() {
+                let index = self.add_url_dialog.choice_engine.find_index(engine);
+                if index >= 0 {
+                    self.add_url_dialog.choice_engine.set_value(index);
+                }
+            }
+            if let Some(output_dir) = rule.output_dir.as_ref() {
+                self.add_url_dialog.input_dir.set_value(output_dir);
+            }
+            if let Some(cookie_file) = rule.cookie_file.as_ref() {
+                match std::fs::read_to_string(cookie_file) {
+                    Ok(cookies) => self.rule_cookies = Some(cookies),
+                    Err(error) => self
+                        .add_url_dialog
+                        .set_status_bar_error(&format!("Failed to read {}: {}", cookie_file, error)),
+                }
+            }
+            self.add_url_dialog
+                .set_status_bar_message(&format!("Rule {} applied", rule.pattern));
+        }
+
+        self.current_rule = rule;
+    }
+
+    /// Undoes engine and output dir set by current rule, unless changed by hand since.
+    fn clear_rule(&mut self) {
+        if let Some(rule) = self.current_rule.take() {
+            if rule.engine.is_some() && self.add_url_dialog.choice_engine.choice() == rule.engine {
+                self.add_url_dialog.choice_engine.set_value(0);
+            }
+            if rule.output_dir.is_some()
+                && Some(self.add_url_dialog.input_dir.value()) == rule.output_dir
+            {
+                self.add_url_dialog
+                    .input_dir
+                    .set_value(&get_default_dir().unwrap_or_default());
+            }
+        }
+        self.rule_cookies = None;
+    }
+
+    fn get_cookies(&self) -> Option<String> {
+        self.current_cookies.clone().or(self.rule_cookies.clone())
     }
 
     fn set_cookies(&mut self) {
@@ -156,15 +287,17 @@ impl AddUrlDialog {
     fn reset(&mut self) {
         self.add_url_dialog.input_url.set_value("");
         self.add_url_dialog.output_title.set_value("");
-        self.add_url_dialog.checkbrowser.clear();
+        self.stream_table.clear();
+        self.add_url_dialog.input_stop_after.set_value("");
+        self.add_url_dialog.input_stop_at.set_value("");
+        self.add_url_dialog.input_start_at.set_value("");
+        self.add_url_dialog.input_run_window.set_value("");
+        self.clear_rule();
     }
 
     fn check_all(&mut self) {
-        if self.add_url_dialog.check_all.is_checked() {
-            self.add_url_dialog.checkbrowser.check_all();
-        } else {
-            self.add_url_dialog.checkbrowser.check_none();
-        }
+        self.stream_table
+            .check_all(self.add_url_dialog.check_all.is_checked());
     }
 
     fn select_dir(&mut self) {
@@ -175,6 +308,52 @@ impl AddUrlDialog {
         }
     }
 
+    fn get_record_limit(&self) -> Result<Option<RecordLimit>> {
+        let stop_after = self.add_url_dialog.input_stop_after.value();
+        let stop_after = match stop_after.trim() {
+            "" => None,
+            minutes => {
+                let minutes: u64 = minutes
+                    .parse()
+                    .map_err(|_| anyhow!("Stop after should be minutes!"))?;
+                Some(std::time::Duration::from_secs(minutes * 60))
+            }
+        };
+
+        let stop_at = self.add_url_dialog.input_stop_at.value();
+        let stop_at = match stop_at.trim() {
+            "" => None,
+            time => Some(
+                chrono::NaiveTime::parse_from_str(time, "%H:%M")
+                    .map_err(|_| anyhow!("Stop at should be HH:MM!"))?,
+            ),
+        };
+
+        match stop_after.is_some() || stop_at.is_some() {
+            true => Ok(Some(RecordLimit { stop_after, stop_at })),
+            false => Ok(None),
+        }
+    }
+
+    fn get_schedule(&self) -> Result<Option<TaskSchedule>> {
+        let start_at = match self.add_url_dialog.input_start_at.value().trim() {
+            "" => None,
+            time => Some(TaskSchedule::next_time(
+                chrono::NaiveTime::parse_from_str(time, "%H:%M")
+                    .map_err(|_| anyhow!("Start at should be HH:MM!"))?,
+            )),
+        };
+        let window = match self.add_url_dialog.input_run_window.value().trim() {
+            "" => None,
+            window => Some(TimeWindow::parse(window)?),
+        };
+
+        match start_at.is_some() || window.is_some() {
+            true => Ok(Some(TaskSchedule { start_at, window })),
+            false => Ok(None),
+        }
+    }
+
     fn submit(&mut self) {
         let mut current_task: Vec<DownloadInfo> = Vec::new();
 
@@ -186,17 +365,36 @@ impl AddUrlDialog {
             return;
         }
 
-        for i in 1..=self.add_url_dialog.checkbrowser.nitems() as i32 {
-            if self.add_url_dialog.checkbrowser.checked(i) {
-                if let Some(info) = self.current_idx.get(&i) {
-                    let mut info = info.to_owned();
-                    info.save_option = Some(SaveOption {
-                        output_dir: save_dir.clone(),
-                        file_name: format!("{}[{}]", info.title, info.stream_name),
-                    });
-                    current_task.push(info);
-                }
+        let record_limit = match self.get_record_limit() {
+            Ok(record_limit) => record_limit,
+            Err(error) => {
+                self.add_url_dialog.set_status_bar_error(&error.to_string());
+                return;
+            }
+        };
+        let schedule = match self.get_schedule() {
+            Ok(schedule) => schedule,
+            Err(error) => {
+                self.add_url_dialog.set_status_bar_error(&error.to_string());
+                return;
+            }
+        };
+
+        for mut info in self.stream_table.get_checked() {
+            let file_name = match self.current_rule.as_ref().and_then(|x| x.file_name.as_ref()) {
+                Some(template) => expand_file_name(template, &info),
+                None => format!("{}[{}]", info.title, info.stream_name),
+            };
+            info.save_option = Some(SaveOption {
+                output_dir: save_dir.clone(),
+                file_name,
+            });
+            info.cookies = self.get_cookies();
+            if info.is_live {
+                info.record_limit = record_limit.clone();
             }
+            info.schedule = schedule.clone();
+            current_task.push(info);
         }
 
         let length = current_task.len();
@@ -221,16 +419,30 @@ impl AddUrlDialog {
                 }
             }
             AddUrlDialogMessage::Show => self.add_url_dialog.window.show(),
+            AddUrlDialogMessage::ShowUrl(url) => self.show_url(&url),
             AddUrlDialogMessage::Hide => self.add_url_dialog.window.hide(),
             AddUrlDialogMessage::Submit => self.submit(),
             AddUrlDialogMessage::SelectDir => self.select_dir(),
             AddUrlDialogMessage::CheckAll => self.check_all(),
             AddUrlDialogMessage::Reset => self.reset(),
+            AddUrlDialogMessage::UrlChanged => self.apply_rule(),
+            AddUrlDialogMessage::FilterStreams => {
+                let filter = StreamFilter::from_index(self.add_url_dialog.choice_stream_filter.value());
+                self.stream_table.set_filter(filter);
+            }
             AddUrlDialogMessage::SetCookies => self.set_cookies(),
+            AddUrlDialogMessage::SetOutputDir(dir) => self.add_url_dialog.input_dir.set_value(&dir),
         }
     }
 }
 
+fn get_default_dir() -> Option<String> {
+    get_config().output_dir.or_else(|| {
+        directories::UserDirs::new()
+            .and_then(|x| x.download_dir().map(|p| p.to_string_lossy().to_string()))
+    })
+}
+
 #[derive(Clone)]
 pub enum AddUrlDialogMessage {
     UpdateInfo(Arc<HashMap<String, DownloadInfo>>),
@@ -238,10 +450,15 @@ pub enum AddUrlDialogMessage {
     Detect,
     SelectDir,
     Show,
+    /// Shows dialog with the url filled and detecting.
+    ShowUrl(String),
     Hide,
     CheckAll,
     Reset,
     SetCookies,
+    SetOutputDir(String),
+    UrlChanged,
+    FilterStreams,
 }
 
 impl From<AddUrlDialogMessage> for AppMessage {
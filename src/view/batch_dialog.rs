@@ -0,0 +1,412 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use fltk::{prelude::*, *};
+use fltk_table::SmartTable;
+
+use crate::{
+    archive::{is_archived, is_url_archived},
+    config::{get_config, DomainRule},
+    downloader::*,
+    send_message, AppMessage,
+};
+
+use super::{MainFormMessage, StatusBar};
+
+mod ui {
+    fl2rust_macro::include_ui!("./src/ui/batch.fl");
+}
+
+#[derive(Clone)]
+enum BatchStatus {
+    Pending,
+    Detecting,
+    Detected(Arc<HashMap<String, DownloadInfo>>),
+    Failed(String),
+    Queued(usize),
+    /// Downloaded before, according to download archive.
+    Archived,
+}
+
+impl BatchStatus {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Pending => "Pending".to_owned(),
+            Self::Detecting => "Detecting".to_owned(),
+            Self::Detected(info_map) => format!("{} stream(s)", info_map.len()),
+            Self::Failed(error) => format!("Failed: {}", error),
+            Self::Queued(count) => format!("{} task(s) queued", count),
+            Self::Archived => "Archived".to_owned(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct BatchItem {
+    url: String,
+    rule: Option<DomainRule>,
+    status: BatchStatus,
+}
+
+#[derive(Clone)]
+pub struct BatchDialog {
+    ui: ui::UserInterface,
+    table: SmartTable,
+    items: Vec<BatchItem>,
+    /// Bumped on clear, so that results of earlier detecting are dropped.
+    generation: usize,
+}
+
+impl BatchDialog {
+    pub fn default() -> Self {
+        let mut ui = ui::UserInterface::make_window();
+
+        let mut table = SmartTable::default();
+        Self::set_table_opts(&mut table);
+        ui.table_parent.add_resizable(&*table);
+        let table = table.size_of_parent().center_of_parent();
+
+        let mut engine_names = vec![AUTO_ENGINE.to_owned()];
+        engine_names.extend(get_engine_names());
+        ui.choice_engine.add_choice(engine_names.join("|").as_str());
+        ui.choice_engine.set_value(0);
+
+        let config = get_config();
+        if let Some(download_dir) = config.output_dir.or_else(|| {
+            directories::UserDirs::new()
+                .and_then(|x| x.download_dir().map(|p| p.to_string_lossy().to_string()))
+        }) {
+            ui.input_dir.set_value(&download_dir);
+        }
+        ui.input_preference
+            .set_value(&config.stream_preference.unwrap_or("best".to_owned()));
+
+        let mut result = Self {
+            ui,
+            table,
+            items: Vec::new(),
+            generation: 0,
+        };
+
+        result.bind_message();
+        result
+    }
+
+    fn bind_message(&mut self) {
+        self.ui
+            .btn_import
+            .set_callback(|_| send_message(BatchDialogMessage::ImportFile));
+        self.ui
+            .btn_paste
+            .set_callback(|_| send_message(BatchDialogMessage::Paste));
+        self.ui
+            .btn_detect
+            .set_callback(|_| send_message(BatchDialogMessage::Detect));
+        self.ui
+            .btn_select_dir
+            .set_callback(|_| send_message(BatchDialogMessage::SelectDir));
+        self.ui
+            .btn_submit
+            .set_callback(|_| send_message(BatchDialogMessage::Submit));
+        self.ui
+            .btn_clear
+            .set_callback(|_| send_message(BatchDialogMessage::Clear));
+        self.ui
+            .btn_close
+            .set_callback(|_| send_message(BatchDialogMessage::Hide));
+    }
+
+    /// Adds urls found in `text` to the input, skipping those already there.
+    fn import_text(&mut self, text: &str) {
+        let mut urls = extract_urls(&self.ui.input_urls.value());
+        let count = urls.len();
+        for url in import_urls(text) {
+            if urls.contains(&url) == false {
+                urls.push(url);
+            }
+        }
+
+        self.ui.input_urls.set_value(&urls.join("\n"));
+        self.ui
+            .set_status_bar_message(&format!("{} url(s) imported", urls.len() - count));
+    }
+
+    fn import_file(&mut self) {
+        if let Some(file) =
+            dialog::file_chooser("Choose url list", "*.{txt,csv,url,webloc}", "", false)
+        {
+            match std::fs::read_to_string(&file) {
+                Ok(content) => self.import_text(&content),
+                Err(error) => self
+                    .ui
+                    .set_status_bar_error(&format!("Failed to read {}: {}", file, error)),
+            }
+        }
+    }
+
+    fn paste(&mut self) {
+        // The clipboard text arrives as a paste event of the input
+        app::paste_text(&self.ui.input_urls);
+    }
+
+    fn select_dir(&mut self) {
+        if let Some(dir) = dialog::dir_chooser("Choose dir to save download file", "", false) {
+            self.ui.input_dir.set_value(&dir);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.generation = self.generation + 1;
+        self.items.clear();
+        self.ui.input_urls.set_value("");
+        self.update_rows();
+    }
+
+    /// Detects all new urls in a bounded worker pool, skipping archived ones.
+    fn detect(&mut self) {
+        let skip_archived = get_config().skip_archived;
+        for url in extract_urls(&self.ui.input_urls.value()) {
+            if self.items.iter().any(|x| x.url == url) == false {
+                let status = match skip_archived && is_url_archived(&url) {
+                    true => BatchStatus::Archived,
+                    false => BatchStatus::Pending,
+                };
+                self.items.push(BatchItem {
+                    rule: get_rule(&url),
+                    url,
+                    status,
+                });
+            }
+        }
+
+        let engine = self
+            .ui
+            .choice_engine
+            .choice()
+            .unwrap_or(AUTO_ENGINE.to_owned());
+        let queue: VecDeque<(usize, String, String, Option<String>)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| matches!(x.status, BatchStatus::Pending | BatchStatus::Failed(_)))
+            .map(|(i, x)| {
+                let engine = x
+                    .rule
+                    .as_ref()
+                    .and_then(|x| x.engine.clone())
+                    .unwrap_or(engine.clone());
+                let cookie_file = x.rule.as_ref().and_then(|x| x.cookie_file.clone());
+                (i, x.url.clone(), engine, cookie_file)
+            })
+            .collect();
+
+        if queue.len() == 0 {
+            self.update_rows();
+            self.ui.set_status_bar_error("No new url to detect!");
+            return;
+        }
+
+        for (i, ..) in &queue {
+            self.items[*i].status = BatchStatus::Pending;
+        }
+        self.update_rows();
+
+        let workers = get_config().batch_workers.max(1).min(queue.len());
+        let generation = self.generation;
+        let queue = Arc::new(Mutex::new(queue));
+        for _ in 0..workers {
+            let queue = queue.clone();
+            std::thread::spawn(move || loop {
+                let (i, url, engine, cookie_file) = match queue.lock().unwrap().pop_front() {
+                    Some(item) => item,
+                    None => break,
+                };
+                send_message(BatchDialogMessage::Detecting(generation, i));
+
+                let cookie_file = cookie_file.and_then(|x| std::fs::read_to_string(x).ok());
+                let cookie_file = cookie_file.and_then(|x| store_cookies(&x).ok());
+                let result = get_stream_info(&engine, &url, cookie_file.as_deref())
+                    .map(|x| Arc::new(x))
+                    .map_err(|x| x.to_string());
+                if let Some(cookie_file) = cookie_file {
+                    let _ = std::fs::remove_file(cookie_file);
+                }
+
+                send_message(BatchDialogMessage::Detected(generation, i, result));
+            });
+        }
+
+        self.ui
+            .set_status_bar_message(&format!("Detecting with {} worker(s)...", workers));
+    }
+
+    fn set_status(&mut self, generation: usize, i: usize, status: BatchStatus) {
+        if generation != self.generation || i >= self.items.len() {
+            return;
+        }
+        self.items[i].status = status;
+        self.update_rows();
+
+        let pending = self
+            .items
+            .iter()
+            .filter(|x| matches!(x.status, BatchStatus::Pending | BatchStatus::Detecting))
+            .count();
+        if pending == 0 {
+            self.ui.set_status_bar_success("All urls are detected!");
+        }
+    }
+
+    /// Queues the preferred streams of each detected url.
+    fn submit(&mut self) {
+        let save_dir = self.ui.input_dir.value();
+        let default_preference = self.ui.input_preference.value();
+        if let Err(error) = Preference::parse(&default_preference) {
+            self.ui.set_status_bar_error(&error.to_string());
+            return;
+        }
+
+        let mut current_task: Vec<DownloadInfo> = Vec::new();
+        for item in self.items.iter_mut() {
+            let info_map = match &item.status {
+                BatchStatus::Detected(info_map) => info_map.clone(),
+                _ => continue,
+            };
+
+            let rule = item.rule.clone().unwrap_or_default();
+            let preference =
+                match Preference::parse(rule.stream.as_ref().unwrap_or(&default_preference)) {
+                    Ok(preference) => preference,
+                    Err(error) => {
+                        item.status = BatchStatus::Failed(error.to_string());
+                        continue;
+                    }
+                };
+
+            let selected = select_tasks(&info_map, &preference, &rule, &save_dir);
+            item.status = match selected.len() {
+                0 => BatchStatus::Failed("No stream matches preference".to_owned()),
+                count => BatchStatus::Queued(count),
+            };
+            current_task.extend(selected);
+        }
+        self.update_rows();
+
+        match current_task.len() {
+            0 => self.ui.set_status_bar_error("No detected url to queue!"),
+            count => {
+                send_message(MainFormMessage::AddTask(Arc::new(current_task)));
+                self.ui
+                    .set_status_bar_success(&format!("{} task(s) queued", count));
+            }
+        }
+    }
+
+    fn update_rows(&mut self) {
+        while self.table.rows() > 0 {
+            self.table.remove_row(self.table.rows() - 1);
+        }
+
+        for item in &self.items {
+            let (engine, title) = match &item.status {
+                BatchStatus::Detected(info_map) => info_map
+                    .values()
+                    .next()
+                    .map(|x| (x.downloader.clone(), x.title.clone()))
+                    .unwrap_or_default(),
+                _ => Default::default(),
+            };
+            self.table
+                .append_row("", &[&item.url, &engine, &title, &item.status.to_string()]);
+        }
+
+        self.table.redraw();
+    }
+
+    fn set_table_opts(table: &mut SmartTable) {
+        table.set_opts(fltk_table::TableOpts {
+            rows: 0,
+            cols: 4,
+            editable: false,
+            cell_border_color: enums::Color::from_rgb(255, 255, 255),
+            cell_align: enums::Align::Left,
+            ..Default::default()
+        });
+        table.set_row_header(false);
+        table.set_row_height_all(20);
+        table.set_col_header_value(0, "Url");
+        table.set_col_header_value(1, "Engine");
+        table.set_col_header_value(2, "Title");
+        table.set_col_header_value(3, "Status");
+        table.set_col_width(0, 200);
+        table.set_col_width(2, 180);
+        table.set_col_width(3, 140);
+    }
+
+    pub fn handle_message(&mut self, message: BatchDialogMessage) {
+        match message {
+            BatchDialogMessage::Show => self.ui.window.show(),
+            BatchDialogMessage::Hide => self.ui.window.hide(),
+            BatchDialogMessage::Import(text) => {
+                self.import_text(&text);
+                self.ui.window.show();
+            }
+            BatchDialogMessage::ImportFile => self.import_file(),
+            BatchDialogMessage::Paste => self.paste(),
+            BatchDialogMessage::SelectDir => self.select_dir(),
+            BatchDialogMessage::Detect => self.detect(),
+            BatchDialogMessage::Detecting(generation, i) => {
+                self.set_status(generation, i, BatchStatus::Detecting)
+            }
+            BatchDialogMessage::Detected(generation, i, result) => {
+                let status = match result {
+                    Ok(info_map)
+                        if get_config().skip_archived && info_map.values().any(|x| is_archived(x)) =>
+                    {
+                        BatchStatus::Archived
+                    }
+                    Ok(info_map) if info_map.len() > 0 => BatchStatus::Detected(info_map),
+                    Ok(_) => BatchStatus::Failed("No stream".to_owned()),
+                    Err(error) => BatchStatus::Failed(error),
+                };
+                self.set_status(generation, i, status)
+            }
+            BatchDialogMessage::Submit => self.submit(),
+            BatchDialogMessage::Clear => self.clear(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum BatchDialogMessage {
+    Show,
+    Hide,
+    /// Imports urls from dropped or pasted text, which may be paths of list files.
+    Import(String),
+    ImportFile,
+    Paste,
+    SelectDir,
+    Detect,
+    Detecting(usize, usize),
+    Detected(
+        usize,
+        usize,
+        Result<Arc<HashMap<String, DownloadInfo>>, String>,
+    ),
+    Submit,
+    Clear,
+}
+
+impl From<BatchDialogMessage> for AppMessage {
+    fn from(value: BatchDialogMessage) -> Self {
+        AppMessage::BatchDialog(value)
+    }
+}
+
+impl StatusBar for ui::UserInterface {
+    fn get_status_bar(&self) -> output::Output {
+        self.output_status.clone()
+    }
+}
@@ -0,0 +1,121 @@
+use fltk::{prelude::*, *};
+
+use crate::{
+    config::{get_config, set_config},
+    downloader::*,
+    send_message, AppMessage,
+};
+
+use super::AddUrlDialogMessage;
+
+mod ui {
+    fl2rust_macro::include_ui!("./src/ui/clipboard.fl");
+}
+
+/// Watches clipboard and offers to add copied urls of supported sites.
+pub struct ClipboardMonitor {
+    ui: ui::UserInterface,
+    url: String,
+}
+
+impl ClipboardMonitor {
+    pub fn default() -> Self {
+        let ui = ui::UserInterface::make_window();
+
+        // Clipboard text arrives as a paste event of this hidden receiver
+        ui.window.begin();
+        let mut receiver = frame::Frame::default();
+        ui.window.end();
+        receiver.hide();
+        receiver.handle({
+            let mut last: Option<String> = None;
+            move |_, event| match event {
+                enums::Event::Paste => {
+                    let text = app::event_text();
+                    // Text already in clipboard on startup is not offered
+                    if last.as_ref().map(|x| x != &text).unwrap_or(false) {
+                        if let Some(url) = extract_urls(&text)
+                            .into_iter()
+                            .find(|x| is_supported_url(x))
+                        {
+                            send_message(ClipboardMessage::Found(url));
+                        }
+                    }
+                    last = Some(text);
+                    true
+                }
+                _ => false,
+            }
+        });
+
+        app::add_timeout3(1.0, move |handle| {
+            if get_config().watch_clipboard {
+                app::paste_text(&receiver);
+            }
+            app::repeat_timeout3(1.0, handle);
+        });
+
+        let mut result = Self {
+            ui,
+            url: String::new(),
+        };
+
+        result.bind_message();
+        result
+    }
+
+    fn bind_message(&mut self) {
+        self.ui
+            .btn_add
+            .set_callback(|_| send_message(ClipboardMessage::Add));
+        self.ui
+            .btn_ignore
+            .set_callback(|_| send_message(ClipboardMessage::Ignore));
+        self.ui
+            .btn_stop
+            .set_callback(|_| send_message(ClipboardMessage::StopWatching));
+    }
+
+    fn found(&mut self, url: String) {
+        self.ui.output_url.set_value(&url);
+        self.url = url;
+        self.ui.window.show();
+    }
+
+    fn add(&mut self) {
+        self.ui.window.hide();
+        send_message(AddUrlDialogMessage::ShowUrl(self.url.clone()));
+    }
+
+    fn stop_watching(&mut self) {
+        self.ui.window.hide();
+        let mut config = get_config();
+        config.watch_clipboard = false;
+        if let Err(error) = set_config(config) {
+            dialog::alert_default(&format!("Failed to save config: {}", error));
+        }
+    }
+
+    pub fn handle_message(&mut self, message: ClipboardMessage) {
+        match message {
+            ClipboardMessage::Found(url) => self.found(url),
+            ClipboardMessage::Add => self.add(),
+            ClipboardMessage::Ignore => self.ui.window.hide(),
+            ClipboardMessage::StopWatching => self.stop_watching(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum ClipboardMessage {
+    Found(String),
+    Add,
+    Ignore,
+    StopWatching,
+}
+
+impl From<ClipboardMessage> for AppMessage {
+    fn from(value: ClipboardMessage) -> Self {
+        AppMessage::Clipboard(value)
+    }
+}
@@ -3,9 +3,17 @@ use std::{collections::HashMap, path::PathBuf};
 use anyhow::Result;
 use fltk::{prelude::*, *};
 
-use crate::{downloader::*, send_message, AppMessage};
-
-use super::{tool_downloader::Task, utils::*, ToolDownloaderMessage};
+use crate::{
+    config::{get_config, set_config},
+    downloader::*,
+    send_message, AppMessage,
+};
+
+use super::{
+    tool_downloader::{PluginTarget, Task},
+    utils::*,
+    MainFormMessage, ToolDownloaderMessage,
+};
 
 mod ui {
     fl2rust_macro::include_ui!("./src/ui/engine_manager.fl");
@@ -15,6 +23,7 @@ mod ui {
 pub struct EngineManager {
     pub engine_manager: ui::UserInterface,
     current_download_asset: Option<HashMap<String, String>>,
+    current_local_version: Option<String>,
 }
 
 impl EngineManager {
@@ -29,18 +38,18 @@ impl EngineManager {
 
         engine_manager.choice_engine.set_value(0);
 
-        engine_manager
-            .choice_mirror
-            .add_choice("github.com|ghproxy.com|kgithub.com");
-
-        engine_manager.choice_mirror.set_value(0);
-
         let mut result = Self {
             engine_manager,
             current_download_asset,
+            current_local_version: None,
         };
 
         result.bind_message();
+        result.update_mirrors();
+        if let Some(engine_name) = result.engine_manager.choice_engine.choice() {
+            result.update_venv_buttons(&engine_name);
+            result.update_installed_versions(&engine_name);
+        }
         result
     }
 
@@ -54,6 +63,36 @@ impl EngineManager {
         self.engine_manager
             .btn_download_extra
             .set_callback(|_| send_message(EngineManagerMessage::DownloadExtra));
+        self.engine_manager
+            .btn_venv_install
+            .set_callback(|_| send_message(EngineManagerMessage::VenvInstall));
+        self.engine_manager
+            .btn_venv_uninstall
+            .set_callback(|_| send_message(EngineManagerMessage::VenvUninstall));
+        self.engine_manager
+            .choice_mirror
+            .set_callback(|_| send_message(EngineManagerMessage::SelectMirror));
+        self.engine_manager
+            .btn_pin
+            .set_callback(|_| send_message(EngineManagerMessage::PinVersion));
+        self.engine_manager
+            .btn_rollback
+            .set_callback(|_| send_message(EngineManagerMessage::Rollback));
+    }
+
+    /// Reads engine manifests again, so that edited ones show up without restarting.
+    fn reload_engines(&mut self) {
+        let errors = reload_manifests();
+        let current = self.engine_manager.choice_engine.choice();
+        let choice_engine = &mut self.engine_manager.choice_engine;
+        choice_engine.clear();
+        choice_engine.add_choice(get_engine_names().join("|").as_str());
+        let index = current.map(|x| choice_engine.find_index(&x)).unwrap_or(0);
+        choice_engine.set_value(index.max(0));
+
+        if errors.len() > 0 {
+            dialog::alert_default(&format!("Failed to load engines:\n{}", errors.join("\n")));
+        }
     }
 
     pub fn prepar_detecting(&mut self) {
@@ -68,11 +107,30 @@ impl EngineManager {
         self.engine_manager.output_version.set_value("Detecting...");
         self.engine_manager.output_count.set_value("-");
         self.engine_manager.choice_assets.clear();
+        self.engine_manager.box_update.set_label("");
+        self.current_local_version = None;
     }
 
     pub fn update_program_local_info(&mut self, location: &str, version: &str) {
         self.engine_manager.output_location.set_value(location);
         self.engine_manager.output_local_version.set_value(version);
+        self.current_local_version = Some(version.to_owned());
+    }
+
+    fn update_indicator(&mut self, latest_version: &str) {
+        let local_version = self.current_local_version.clone().unwrap_or_default();
+        let box_update = &mut self.engine_manager.box_update;
+
+        if EngineVersion::parse(&local_version).is_none() {
+            box_update.set_label("");
+        } else if is_update_available(&local_version, latest_version) {
+            box_update.set_label_color(enums::Color::Red);
+            box_update.set_label("Update available!");
+        } else {
+            box_update.set_label_color(enums::Color::DarkGreen);
+            box_update.set_label("Up to date");
+        }
+        box_update.redraw();
     }
 
     pub fn update_program_info(&mut self, latest_release: &GithubLatestRelease) {
@@ -94,6 +152,7 @@ impl EngineManager {
         self.engine_manager.choice_assets.set_value(0);
 
         self.current_download_asset = Some(latest_release.download_assets.to_hashmap());
+        self.update_indicator(&latest_release.version);
     }
 
     pub fn detect(&mut self) {
@@ -105,6 +164,9 @@ impl EngineManager {
         }
         let engine_name = engine_name.unwrap();
 
+        self.update_venv_buttons(&engine_name);
+        self.update_installed_versions(&engine_name);
+
         std::thread::spawn(move || {
             let (location, version) = get_engine(&engine_name)
                 .ok()
@@ -120,12 +182,151 @@ impl EngineManager {
         });
     }
 
+    fn update_installed_versions(&mut self, engine_name: &str) {
+        let versions = get_installed_versions(engine_name);
+        let pinned = get_pinned_version(engine_name);
+
+        let choice_installed = &mut self.engine_manager.choice_installed;
+        choice_installed.clear();
+        choice_installed.add_choice("latest");
+        choice_installed.set_value(0);
+
+        for (i, version) in versions.iter().enumerate() {
+            choice_installed.add_choice(version);
+            if pinned.as_ref() == Some(version) {
+                choice_installed.set_value(i as i32 + 1);
+            }
+        }
+    }
+
+    fn pin_version(&mut self) -> Result<()> {
+        let engine_name = self
+            .engine_manager
+            .choice_engine
+            .choice()
+            .ok_or_else(|| anyhow::anyhow!("Please select engine"))?;
+
+        let version = match self.engine_manager.choice_installed.value() {
+            index if index > 0 => self.engine_manager.choice_installed.choice(),
+            _ => None,
+        };
+
+        pin_version(&engine_name, version.as_deref())?;
+        self.detect();
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> Result<()> {
+        let engine_name = self
+            .engine_manager
+            .choice_engine
+            .choice()
+            .ok_or_else(|| anyhow::anyhow!("Please select engine"))?;
+
+        let version = rollback_version(&engine_name)?;
+        dialog::message_default(&format!("{engine_name} is rolled back to {version}."));
+        self.detect();
+        Ok(())
+    }
+
+    fn update_venv_buttons(&mut self, engine_name: &str) {
+        if get_pip_package(engine_name).is_some() {
+            self.engine_manager.btn_venv_install.activate();
+            self.engine_manager.btn_venv_uninstall.activate();
+        } else {
+            self.engine_manager.btn_venv_install.deactivate();
+            self.engine_manager.btn_venv_uninstall.deactivate();
+        }
+    }
+
+    fn venv_install(&mut self) {
+        let engine_name = self.engine_manager.choice_engine.choice().unwrap_or_default();
+        let package = match get_pip_package(&engine_name) {
+            Some(package) => package,
+            None => return,
+        };
+
+        let latest_version = self.engine_manager.output_version.value();
+        let default_version = match EngineVersion::parse(&latest_version) {
+            Some(_) => latest_version,
+            None => "".to_owned(),
+        };
+
+        let version = match dialog::input_default(
+            &format!("Version of {package} to install (empty for latest, or a local wheel):"),
+            &default_version,
+        ) {
+            Some(version) => version,
+            None => return,
+        };
+
+        self.engine_manager.btn_venv_install.deactivate();
+        self.engine_manager.btn_venv_uninstall.deactivate();
+        self.engine_manager.box_update.set_label("Installing...");
+        self.engine_manager.box_update.redraw();
+
+        std::thread::spawn(move || {
+            let error = install_venv_package(&engine_name, &package, Some(version.as_str()))
+                .err()
+                .map(|x| x.to_string());
+            send_message(EngineManagerMessage::VenvFinished(error));
+        });
+    }
+
+    fn venv_uninstall(&mut self) {
+        let engine_name = self.engine_manager.choice_engine.choice().unwrap_or_default();
+        if get_pip_package(&engine_name).is_none() {
+            return;
+        }
+
+        let confirm = dialog::choice2_default(
+            &format!("Remove the venv of {engine_name}?"),
+            "Cancel",
+            "Remove",
+            "",
+        );
+        if confirm != Some(1) {
+            return;
+        }
+
+        self.venv_finished(uninstall_venv(&engine_name).err().map(|x| x.to_string()));
+    }
+
+    fn venv_finished(&mut self, error: Option<String>) {
+        if let Some(error) = error {
+            dialog::alert_default(&format!("Failed to manage venv: {}", error));
+        }
+        self.detect();
+    }
+
+    fn update_mirrors(&mut self) {
+        let config = get_config();
+        let choice_mirror = &mut self.engine_manager.choice_mirror;
+        choice_mirror.clear();
+        choice_mirror.set_value(0);
+
+        for (i, mirror) in config.mirrors.iter().enumerate() {
+            choice_mirror.add_choice(&mirror.name);
+            if mirror.name == config.github_mirror {
+                choice_mirror.set_value(i as i32);
+            }
+        }
+    }
+
+    fn select_mirror(&mut self) -> Result<()> {
+        let mut config = get_config();
+        if let Some(mirror) = self.engine_manager.choice_mirror.choice() {
+            config.github_mirror = mirror;
+            set_config(config)?;
+        }
+        Ok(())
+    }
+
     fn replace_download_url(&self, url: &str) -> String {
-        let mut url = url.to_owned();
-        if let Some(choice_mirror) = self.engine_manager.choice_mirror.choice() {
-            url = replace_github_download(&url, &choice_mirror);
+        match get_config().get_mirror() {
+            Some(mirror) => replace_github_download(url, mirror),
+            None => url.to_owned(),
         }
-        url
     }
 
     fn get_asset_name(&self) -> String {
@@ -159,15 +360,23 @@ impl EngineManager {
             url: self.replace_download_url(&url),
             output_path: output_path.to_string_lossy().to_string(),
             message: format!("Downloading {filename}..."),
-            to_plugin: false,
+            plugin: None,
         }));
 
         Ok(())
     }
 
-    #[cfg(target_os = "windows")]
     pub fn download_to_plugins(&self) -> Result<()> {
         let url = self.get_asset_url()?;
+        let program = self
+            .engine_manager
+            .choice_engine
+            .choice()
+            .ok_or_else(|| anyhow::anyhow!("Please select engine"))?;
+        let version = self.engine_manager.output_version.value();
+        if EngineVersion::parse(&version).is_none() {
+            return Err(anyhow::anyhow!("Latest version is unknown"));
+        }
         let filename = get_filename_from_url(&url).unwrap_or("".to_owned());
 
         let output_path = std::env::temp_dir().join(&filename);
@@ -180,21 +389,17 @@ impl EngineManager {
             url: self.replace_download_url(&url),
             output_path: output_path.to_string_lossy().to_string(),
             message: format!("Downloading {}...", filename),
-            to_plugin: true,
+            plugin: Some(PluginTarget { program, version }),
         }));
 
         Ok(())
     }
 
-    #[cfg(target_os = "windows")]
     pub fn download_and_extract(&self) -> Result<()> {
         self.download_to_plugins()?;
         Ok(())
     }
 
-    #[cfg(not(target_os = "windows"))]
-    fn download_and_extract(&self) {}
-
     pub fn handle_message(&mut self, message: EngineManagerMessage) {
         match message {
             EngineManagerMessage::UpdateLocal(location, version) => {
@@ -208,14 +413,66 @@ impl EngineManager {
                 let _ = self.download();
             }
             EngineManagerMessage::DownloadExtra => {
-                let _ = self.download_and_extract();
+                if let Err(error) = self.download_and_extract() {
+                    dialog::alert_default(&error.to_string());
+                }
+            }
+            EngineManagerMessage::PinVersion => {
+                if let Err(error) = self.pin_version() {
+                    dialog::alert_default(&error.to_string());
+                }
+            }
+            EngineManagerMessage::Rollback => {
+                if let Err(error) = self.rollback() {
+                    dialog::alert_default(&error.to_string());
+                }
+            }
+            EngineManagerMessage::VenvInstall => self.venv_install(),
+            EngineManagerMessage::VenvUninstall => self.venv_uninstall(),
+            EngineManagerMessage::VenvFinished(error) => self.venv_finished(error),
+            EngineManagerMessage::SelectMirror => {
+                if let Err(error) = self.select_mirror() {
+                    dialog::alert_default(&error.to_string());
+                }
+            }
+            EngineManagerMessage::Show => {
+                self.reload_engines();
+                self.update_mirrors();
+                self.engine_manager.window.show();
             }
-            EngineManagerMessage::Show => self.engine_manager.window.show(),
             EngineManagerMessage::Hide => self.engine_manager.window.hide(),
         }
     }
 }
 
+/// Compares every installed engine with its latest release in background and
+/// reports the outdated ones to main form.
+pub fn check_engine_updates(report_up_to_date: bool) {
+    std::thread::spawn(move || {
+        let mut outdated = Vec::new();
+
+        for engine_name in get_engine_names() {
+            let local_version = match get_engine(&engine_name).and_then(|x| x.get_program()) {
+                Ok((_, version)) => version,
+                Err(_) => continue,
+            };
+
+            if let Some(latest_release) = GithubLatestRelease::get_from_github_api(&engine_name) {
+                if is_update_available(&local_version, &latest_release.version) {
+                    outdated.push(format!(
+                        "{} ({} -> {})",
+                        engine_name, local_version, latest_release.version
+                    ));
+                }
+            }
+        }
+
+        if outdated.len() > 0 || report_up_to_date {
+            send_message(MainFormMessage::EngineOutdated(outdated));
+        }
+    });
+}
+
 fn select_store_path(default_filename: &str) -> Result<PathBuf> {
     let mut dialog = dialog::FileDialog::new(dialog::FileDialogType::BrowseSaveFile);
     dialog.set_title("Save downloaded assert ...");
@@ -242,6 +499,12 @@ pub enum EngineManagerMessage {
     Detect,
     Download,
     DownloadExtra,
+    SelectMirror,
+    PinVersion,
+    Rollback,
+    VenvInstall,
+    VenvUninstall,
+    VenvFinished(Option<String>),
     Show,
     Hide,
 }
@@ -0,0 +1,335 @@
+use std::{path::Path, sync::Arc};
+
+use chrono::{NaiveDate, TimeZone};
+use fltk::{prelude::*, *};
+use fltk_table::SmartTable;
+
+use crate::{
+    archive::{export_archive, import_archive},
+    downloader::DownloadInfo,
+    history::{load_history, HistoryEntry, HistoryFilter, Outcome},
+    send_message, AppMessage,
+};
+
+use super::{utils::*, MainFormMessage, StatusBar};
+
+mod ui {
+    fl2rust_macro::include_ui!("./src/ui/history.fl");
+}
+
+#[derive(Clone)]
+pub struct HistoryDialog {
+    ui: ui::UserInterface,
+    table: SmartTable,
+    entries: Vec<HistoryEntry>,
+    /// Indexes of `entries` in table rows, newest first.
+    rows: Vec<usize>,
+}
+
+impl HistoryDialog {
+    pub fn default() -> Self {
+        let mut ui = ui::UserInterface::make_window();
+
+        let mut table = SmartTable::default();
+        Self::set_table_opts(&mut table);
+        table.handle(|tb, event| {
+            if event == enums::Event::Released {
+                let (row_top, _, row_button, _) = tb.get_selection();
+                tb.set_selection(row_top, 0, row_button, 6);
+            }
+            false
+        });
+        ui.table_parent.add_resizable(&*table);
+        let table = table.size_of_parent().center_of_parent();
+
+        ui.choice_outcome.add_choice("All");
+        ui.choice_outcome
+            .add_choice(Outcome::NAMES.join("|").as_str());
+        ui.choice_outcome.set_value(0);
+
+        let mut result = Self {
+            ui,
+            table,
+            entries: Vec::new(),
+            rows: Vec::new(),
+        };
+
+        result.bind_message();
+        result
+    }
+
+    fn bind_message(&mut self) {
+        for input in [
+            &mut self.ui.input_search,
+            &mut self.ui.input_from,
+            &mut self.ui.input_to,
+        ] {
+            input.set_trigger(enums::CallbackTrigger::Changed);
+            input.set_callback(|_| send_message(HistoryDialogMessage::Search));
+        }
+        self.ui
+            .choice_outcome
+            .set_callback(|_| send_message(HistoryDialogMessage::Search));
+        self.ui
+            .btn_requeue
+            .set_callback(|_| send_message(HistoryDialogMessage::Requeue));
+        self.ui
+            .btn_open_file
+            .set_callback(|_| send_message(HistoryDialogMessage::OpenFile));
+        self.ui
+            .btn_open_folder
+            .set_callback(|_| send_message(HistoryDialogMessage::OpenFolder));
+        self.ui
+            .btn_import_archive
+            .set_callback(|_| send_message(HistoryDialogMessage::ImportArchive));
+        self.ui
+            .btn_export_archive
+            .set_callback(|_| send_message(HistoryDialogMessage::ExportArchive));
+        self.ui
+            .btn_refresh
+            .set_callback(|_| send_message(HistoryDialogMessage::Refresh));
+        self.ui
+            .btn_close
+            .set_callback(|_| send_message(HistoryDialogMessage::Hide));
+    }
+
+    fn refresh(&mut self) {
+        self.entries = load_history();
+        self.search();
+    }
+
+    fn get_filter(&self) -> Result<HistoryFilter, String> {
+        let parse_date = |input: &input::Input| {
+            let value = input.value();
+            match value.trim().len() {
+                0 => Ok(None),
+                _ => NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+                    .map(Some)
+                    .map_err(|_| format!("{} is not a date like 2024-05-20", value.trim())),
+            }
+        };
+
+        Ok(HistoryFilter {
+            text: self.ui.input_search.value(),
+            from: parse_date(&self.ui.input_from)?,
+            to: parse_date(&self.ui.input_to)?,
+            outcome: match self.ui.choice_outcome.value() {
+                1 => Some(Outcome::Finished),
+                2 => Some(Outcome::Failed),
+                3 => Some(Outcome::Stopped),
+                _ => None,
+            },
+        })
+    }
+
+    fn search(&mut self) {
+        let filter = match self.get_filter() {
+            Ok(filter) => filter,
+            Err(error) => {
+                self.ui.set_status_bar_error(&error);
+                return;
+            }
+        };
+
+        self.rows = (0..self.entries.len())
+            .rev()
+            .filter(|i| filter.is_match(&self.entries[*i]))
+            .collect();
+        self.update_rows();
+        self.ui.set_status_bar_message(&format!(
+            "{} of {} download(s) shown",
+            self.rows.len(),
+            self.entries.len()
+        ));
+    }
+
+    fn update_rows(&mut self) {
+        while self.table.rows() > 0 {
+            self.table.remove_row(self.table.rows() - 1);
+        }
+
+        for i in &self.rows {
+            let entry = &self.entries[*i];
+            let finished_at = chrono::Local
+                .timestamp_opt(entry.finished_at, 0)
+                .single()
+                .map(|x| x.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            self.table.append_row(
+                "",
+                &[
+                    &finished_at,
+                    &entry.title,
+                    &entry.site,
+                    &entry.engine,
+                    &entry.stream_name,
+                    &size_to_string(entry.size as usize),
+                    &entry.outcome.to_string(),
+                ],
+            );
+        }
+
+        self.table.unset_selection();
+        self.table.redraw();
+    }
+
+    fn get_selected(&self) -> Vec<&HistoryEntry> {
+        let (row_top, _, row_bot, _) = self.table.get_selection();
+        if row_top < 0 {
+            return Vec::new();
+        }
+        (row_top..=row_bot)
+            .filter_map(|i| self.rows.get(i as usize))
+            .map(|i| &self.entries[*i])
+            .collect()
+    }
+
+    fn requeue(&mut self) {
+        let tasks: Vec<DownloadInfo> = self
+            .get_selected()
+            .iter()
+            .map(|x| x.to_download_info())
+            .collect();
+        match tasks.len() {
+            0 => self
+                .ui
+                .set_status_bar_error("Please select downloads first!"),
+            count => {
+                send_message(MainFormMessage::RequeueTask(Arc::new(tasks)));
+                self.ui
+                    .set_status_bar_success(&format!("{} task(s) queued again", count));
+            }
+        }
+    }
+
+    fn open(&mut self, folder: bool) {
+        let entry = match self.get_selected().first() {
+            Some(entry) => (*entry).clone(),
+            None => {
+                self.ui
+                    .set_status_bar_error("Please select a download first!");
+                return;
+            }
+        };
+
+        let output = Path::new(&entry.output);
+        let path = match folder {
+            true => output.parent().unwrap_or(Path::new(&entry.output_dir)),
+            false => output,
+        };
+        if path.exists() == false {
+            self.ui
+                .set_status_bar_error(&format!("{} does not exist", path.display()));
+            return;
+        }
+        if let Err(error) = open_path(path) {
+            self.ui.set_status_bar_error(&error.to_string());
+        }
+    }
+
+    fn import_archive(&mut self) {
+        let file = match dialog::file_chooser("Choose youtube-dl archive", "*.txt", "", false) {
+            Some(file) => file,
+            None => return,
+        };
+        match import_archive(Path::new(&file)) {
+            Ok(count) => self
+                .ui
+                .set_status_bar_success(&format!("{} video(s) added to download archive", count)),
+            Err(error) => self
+                .ui
+                .set_status_bar_error(&format!("Failed to import {}: {}", file, error)),
+        }
+    }
+
+    fn export_archive(&mut self) {
+        let mut chooser =
+            dialog::NativeFileChooser::new(dialog::NativeFileChooserType::BrowseSaveFile);
+        chooser.set_title("Save download archive");
+        chooser.set_filter("*.txt");
+        chooser.set_preset_file("archive.txt");
+        chooser.set_option(dialog::NativeFileChooserOptions::SaveAsConfirm);
+        chooser.show();
+
+        let file = chooser.filename();
+        if file.as_os_str().is_empty() {
+            return;
+        }
+        match export_archive(&file) {
+            Ok(count) => self.ui.set_status_bar_success(&format!(
+                "{} video(s) exported to {}",
+                count,
+                file.display()
+            )),
+            Err(error) => self.ui.set_status_bar_error(&format!(
+                "Failed to export {}: {}",
+                file.display(),
+                error
+            )),
+        }
+    }
+
+    fn set_table_opts(table: &mut SmartTable) {
+        table.set_opts(fltk_table::TableOpts {
+            rows: 0,
+            cols: 7,
+            editable: false,
+            cell_border_color: enums::Color::from_rgb(255, 255, 255),
+            cell_align: enums::Align::Left,
+            ..Default::default()
+        });
+        table.set_row_header(false);
+        table.set_row_height_all(20);
+        table.set_col_header_value(0, "Finished");
+        table.set_col_header_value(1, "Title");
+        table.set_col_header_value(2, "Site");
+        table.set_col_header_value(3, "Engine");
+        table.set_col_header_value(4, "Stream");
+        table.set_col_header_value(5, "Size");
+        table.set_col_header_value(6, "Outcome");
+        table.set_col_width(0, 120);
+        table.set_col_width(1, 240);
+    }
+
+    pub fn handle_message(&mut self, message: HistoryDialogMessage) {
+        match message {
+            HistoryDialogMessage::Show => {
+                self.refresh();
+                self.ui.window.show();
+            }
+            HistoryDialogMessage::Hide => self.ui.window.hide(),
+            HistoryDialogMessage::Search => self.search(),
+            HistoryDialogMessage::Refresh => self.refresh(),
+            HistoryDialogMessage::Requeue => self.requeue(),
+            HistoryDialogMessage::OpenFile => self.open(false),
+            HistoryDialogMessage::OpenFolder => self.open(true),
+            HistoryDialogMessage::ImportArchive => self.import_archive(),
+            HistoryDialogMessage::ExportArchive => self.export_archive(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum HistoryDialogMessage {
+    Show,
+    Hide,
+    Search,
+    Refresh,
+    Requeue,
+    OpenFile,
+    OpenFolder,
+    ImportArchive,
+    ExportArchive,
+}
+
+impl From<HistoryDialogMessage> for AppMessage {
+    fn from(value: HistoryDialogMessage) -> Self {
+        AppMessage::HistoryDialog(value)
+    }
+}
+
+impl StatusBar for ui::UserInterface {
+    fn get_status_bar(&self) -> output::Output {
+        self.output_status.clone()
+    }
+}
@@ -2,8 +2,16 @@ use std::sync::Arc;
 
 use fltk::prelude::*;
 
-use crate::{downloader::DownloadInfo, send_message, AppMessage};
+use uuid::Uuid;
 
+use crate::{
+    archive::is_archived,
+    config::{get_config, set_config},
+    downloader::{get_manifest_errors, set_global_limit, DownloadInfo},
+    send_message, AppMessage,
+};
+
+use super::utils::{limit_to_string, SPEED_LIMITS};
 use super::*;
 
 mod mainform_ui {
@@ -15,6 +23,8 @@ use mainform_ui::UserInterface;
 pub struct MainForm {
     ui: UserInterface,
     task_table: TaskTable,
+    /// Global speed limits of status bar choice, 0 for unlimited.
+    speed_limits: Vec<u64>,
 }
 
 impl MainForm {
@@ -28,19 +38,59 @@ impl MainForm {
         fltk::app::add_timeout3(1.0, {
             let mut task_table = task_table.clone();
             move |handle| {
+                task_table.schedule();
                 task_table.update_rows();
                 fltk::app::repeat_timeout3(1.0, handle);
             }
         });
 
-        let mut result = Self { ui, task_table };
+        let mut speed_limits = vec![0];
+        speed_limits.extend(SPEED_LIMITS);
+        let speed_limit = get_config().speed_limit.unwrap_or(0);
+        if speed_limits.contains(&speed_limit) == false {
+            speed_limits.push(speed_limit);
+            speed_limits.sort();
+        }
+        let labels: Vec<String> = speed_limits
+            .iter()
+            .map(|x| limit_to_string(*x).replace('/', "\\/"))
+            .collect();
+        ui.choice_speed_limit.add_choice(&labels.join("|"));
+        let index = speed_limits
+            .iter()
+            .position(|x| *x == speed_limit)
+            .unwrap_or(0);
+        ui.choice_speed_limit.set_value(index as i32);
+
+        let mut result = Self {
+            ui,
+            task_table,
+            speed_limits,
+        };
 
         result.bind_message();
 
+        let errors = get_manifest_errors();
+        if errors.len() > 0 {
+            result
+                .ui
+                .set_status_bar_error(&format!("Failed to load engines: {}", errors.join(", ")));
+        }
+
         return result;
     }
 
     fn bind_message(&mut self) {
+        // Dropped text or files are imported into batch dialog
+        self.ui.window.handle(|_, event| match event {
+            enums::Event::DndEnter | enums::Event::DndDrag | enums::Event::DndRelease => true,
+            enums::Event::Paste => {
+                send_message(BatchDialogMessage::Import(app::event_text()));
+                true
+            }
+            _ => false,
+        });
+
         self.ui
             .btn_add
             .set_callback(|_| send_message(AddUrlDialogMessage::Show));
@@ -61,13 +111,29 @@ impl MainForm {
             .btn_reload
             .set_callback(|_| send_message(MainFormMessage::ReloadTask));
 
+        self.ui.choice_speed_limit.set_callback({
+            let speed_limits = self.speed_limits.clone();
+            move |c| {
+                if let Some(limit) = speed_limits.get(c.value() as usize) {
+                    send_message(MainFormMessage::SetSpeedLimit(*limit));
+                }
+            }
+        });
+
         self.ui.menubar.set_callback(
             move |c| match c.choice().unwrap_or("".to_owned()).as_str() {
                 "Add Url" => send_message(AddUrlDialogMessage::Show),
+                "Batch Add Urls" => send_message(BatchDialogMessage::Show),
+                "History" => send_message(HistoryDialogMessage::Show),
+                "Subscriptions" => send_message(SubscriptionMessage::Show),
+                "Pause All" => send_message(MainFormMessage::PauseAll),
+                "Resume All" => send_message(MainFormMessage::ResumeAll),
                 "Exit" => app::quit(),
                 "README.md" => send_message(MainFormMessage::ShowReadme),
                 "About" => send_message(MainFormMessage::ShowVersion),
                 "Engine Manager" => send_message(EngineManagerMessage::Show),
+                "Check Engine Updates" => send_message(MainFormMessage::CheckEngineUpdates),
+                "Preferences" => send_message(OptionDialogMessage::Show),
                 _ => {}
             },
         );
@@ -81,7 +147,30 @@ impl MainForm {
         }
     }
 
+    /// Queues the tasks, leaving out archived ones unless disabled in options.
     fn add_task(&mut self, download_info: &Vec<DownloadInfo>) {
+        let skip_archived = get_config().skip_archived;
+        let (archived, download_info): (Vec<DownloadInfo>, Vec<DownloadInfo>) = download_info
+            .iter()
+            .cloned()
+            .partition(|x| skip_archived && is_archived(x));
+        let count = self.task_table.add_tasks(&download_info);
+        match archived.len() {
+            0 => self.check_task(
+                count,
+                &format!("{} task(s) added to task table!", count),
+                "No tasks are added to task table!",
+            ),
+            skipped => self.check_task(
+                count,
+                &format!("{} task(s) added, {} skipped as in download archive", count, skipped),
+                &format!("{} task(s) skipped as in download archive", skipped),
+            ),
+        }
+    }
+
+    /// Queues the tasks again on purpose, even if they are archived.
+    fn requeue_task(&mut self, download_info: &Vec<DownloadInfo>) {
         let count = self.task_table.add_tasks(&download_info);
         self.check_task(
             count,
@@ -120,20 +209,113 @@ impl MainForm {
         }
     }
 
+    /// Reports failures of task actions requested by local API.
+    fn control_task(&mut self, result: anyhow::Result<()>) {
+        if let Err(error) = result {
+            self.ui.set_status_bar_error(&error.to_string());
+        }
+    }
+
+    fn set_speed_limit(&mut self, limit: u64) {
+        let limit = (limit > 0).then_some(limit);
+        set_global_limit(limit);
+
+        let mut config = get_config();
+        config.speed_limit = limit;
+        match set_config(config) {
+            Ok(_) => self.ui.set_status_bar_message(&format!(
+                "Speed limit set to {}, engines started before keep the old one.",
+                limit_to_string(limit.unwrap_or(0))
+            )),
+            Err(error) => self.ui.set_status_bar_error(&error.to_string()),
+        }
+    }
+
+    fn set_task_speed_limit(&mut self, limit: Option<u64>) {
+        if let Ok(count) = self.task_table.set_speed_limit_select(limit) {
+            let limit = match limit {
+                Some(limit) => limit_to_string(limit),
+                None => "global speed limit".to_owned(),
+            };
+            self.check_task(
+                count,
+                &format!("The selected {} task(s) use {}.", count, limit),
+                "",
+            );
+        }
+    }
+
     fn reload_task(&mut self) {
         self.task_table.reload();
         self.ui.set_status_bar_message("Task table reloaded.");
     }
 
+    fn check_engine_updates(&mut self) {
+        check_engine_updates(true);
+        self.ui.set_status_bar_message("Checking engine updates...");
+    }
+
+    fn report_outdated(&mut self, outdated: &Vec<String>) {
+        if outdated.len() > 0 {
+            self.ui.set_status_bar_error(&format!(
+                "Outdated engine(s): {}. Update them in Engine Manager!",
+                outdated.join(", ")
+            ));
+        } else {
+            self.ui
+                .set_status_bar_success("All installed engines are up to date.");
+        }
+    }
+
     pub fn handle_message(&mut self, message: MainFormMessage) {
         match message {
             MainFormMessage::AddTask(info) => self.add_task(&*info),
+            MainFormMessage::RequeueTask(info) => self.requeue_task(&*info),
+            MainFormMessage::AddTasksWithIds(tasks) => {
+                let count = self.task_table.add_tasks_with_ids(&*tasks);
+                self.check_task(
+                    count,
+                    &format!("{} task(s) added to task table!", count),
+                    "No tasks are added to task table!",
+                );
+            }
+            MainFormMessage::AddTaskWithId(uuid, info) => {
+                self.task_table.add_task_with_id(uuid, &*info);
+                let result = self.task_table.start_tasks(&vec![uuid]);
+                self.control_task(result)
+            }
             MainFormMessage::StartTask => self.start_task(),
             MainFormMessage::StopTask => self.stop_task(),
             MainFormMessage::DeleteTask => self.delete_task(),
             MainFormMessage::ReloadTask => self.reload_task(),
+            MainFormMessage::PauseAll => {
+                self.task_table.set_paused(true);
+                self.ui
+                    .set_status_bar_message("All tasks paused, they go on after Resume All.");
+            }
+            MainFormMessage::ResumeAll => {
+                self.task_table.set_paused(false);
+                self.ui.set_status_bar_message("All tasks resumed.");
+            }
+            MainFormMessage::SetSpeedLimit(limit) => self.set_speed_limit(limit),
+            MainFormMessage::SetTaskSpeedLimit(limit) => self.set_task_speed_limit(limit),
+            MainFormMessage::StartTaskById(uuid) => {
+                let result = self.task_table.start_tasks(&vec![uuid]);
+                self.control_task(result)
+            }
+            MainFormMessage::StopTaskById(uuid) => {
+                let result = self.task_table.stop_tasks(&vec![uuid]);
+                self.control_task(result)
+            }
+            MainFormMessage::RemoveTaskById(uuid) => {
+                let result = self.task_table.remove_tasks(&vec![uuid]);
+                self.control_task(result)
+            }
             MainFormMessage::ShowReadme => show_readme(),
+            MainFormMessage::Activate => self.ui.window.show(),
             MainFormMessage::ShowVersion => show_about(),
+            MainFormMessage::CheckEngineUpdates => self.check_engine_updates(),
+            MainFormMessage::EngineOutdated(outdated) => self.report_outdated(&outdated),
         }
     }
 }
@@ -167,13 +349,33 @@ fn show_about() {
 
 #[derive(Clone)]
 pub enum MainFormMessage {
+    /// Adds tasks, skipping archived ones if `skip_archived` is set.
     AddTask(Arc<Vec<DownloadInfo>>),
+    /// Adds tasks regardless of download archive, e.g. from history.
+    RequeueTask(Arc<Vec<DownloadInfo>>),
+    /// Adds and starts a task, e.g. from aria2 JSON-RPC.
+    AddTaskWithId(Uuid, Arc<DownloadInfo>),
+    /// Adds tasks with known ids without starting them, e.g. from HTTP API.
+    AddTasksWithIds(Arc<Vec<(Uuid, DownloadInfo)>>),
     StartTask,
     StopTask,
     DeleteTask,
     ReloadTask,
+    PauseAll,
+    ResumeAll,
+    /// Global speed limit in bytes per second, 0 for unlimited.
+    SetSpeedLimit(u64),
+    /// Speed limit of the selected tasks, `None` for the global one.
+    SetTaskSpeedLimit(Option<u64>),
+    StartTaskById(Uuid),
+    StopTaskById(Uuid),
+    RemoveTaskById(Uuid),
     ShowReadme,
     ShowVersion,
+    /// Raises main window, e.g. when launched again.
+    Activate,
+    CheckEngineUpdates,
+    EngineOutdated(Vec<String>),
 }
 
 impl From<MainFormMessage> for AppMessage {
@@ -1,5 +1,11 @@
 mod add_url_dialog;
+mod batch_dialog;
+mod clipboard_monitor;
+mod history_dialog;
 mod mainform;
+mod option_dialog;
+mod stream_table;
+mod subscription_manager;
 mod task_table;
 mod tool_downloader;
 mod utils;
@@ -27,7 +33,13 @@ trait StatusBar {
 }
 
 pub use add_url_dialog::{AddUrlDialog, AddUrlDialogMessage};
+pub use batch_dialog::{BatchDialog, BatchDialogMessage};
+pub use clipboard_monitor::{ClipboardMessage, ClipboardMonitor};
+pub use history_dialog::{HistoryDialog, HistoryDialogMessage};
 pub use mainform::{MainForm, MainFormMessage};
 pub use tool_downloader::{ToolDownloader, ToolDownloaderMessage};
-pub use engine_manager::{EngineManager, EngineManagerMessage};
+pub use engine_manager::{check_engine_updates, EngineManager, EngineManagerMessage};
+pub use option_dialog::{OptionDialog, OptionDialogMessage};
+pub use stream_table::{StreamFilter, StreamTable};
+pub use subscription_manager::{SubscriptionManager, SubscriptionMessage};
 pub use task_table::TaskTable;
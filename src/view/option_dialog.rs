@@ -0,0 +1,283 @@
+use fltk::{prelude::*, *};
+
+use crate::{
+    config::{get_config, set_config, DomainRule, Mirror},
+    downloader::{parse_proxy_url, Preference, TimeWindow},
+    send_message, AppMessage,
+};
+
+use super::AddUrlDialogMessage;
+
+mod ui {
+    fl2rust_macro::include_ui!("./src/ui/option.fl");
+}
+
+#[derive(Clone)]
+pub struct OptionDialog {
+    option_dialog: ui::UserInterface,
+}
+
+impl OptionDialog {
+    pub fn default() -> Self {
+        let option_dialog = ui::UserInterface::make_window();
+
+        let mut result = Self { option_dialog };
+
+        result.bind_message();
+        result.reset();
+        result
+    }
+
+    fn bind_message(&mut self) {
+        self.option_dialog
+            .btn_submit
+            .set_callback(|_| send_message(OptionDialogMessage::Submit));
+        self.option_dialog
+            .btn_reset
+            .set_callback(|_| send_message(OptionDialogMessage::Reset));
+        self.option_dialog
+            .btn_close
+            .set_callback(|_| send_message(OptionDialogMessage::Hide));
+        self.option_dialog
+            .btn_select_dir
+            .set_callback(|_| send_message(OptionDialogMessage::SelectDir));
+    }
+
+    fn reset(&mut self) {
+        let config = get_config();
+
+        self.option_dialog
+            .input_dir
+            .set_value(&config.output_dir.unwrap_or_default());
+        self.option_dialog
+            .check_engine_update
+            .set_checked(config.check_engine_update);
+        self.option_dialog
+            .check_watch_clipboard
+            .set_checked(config.watch_clipboard);
+        self.option_dialog
+            .check_skip_archived
+            .set_checked(config.skip_archived);
+        self.option_dialog
+            .input_engine_fallback
+            .set_value(&config.engine_fallback.join(", "));
+        self.option_dialog
+            .input_stream_preference
+            .set_value(&config.stream_preference.unwrap_or_default());
+        self.option_dialog
+            .input_github_token
+            .set_value(&config.github_token.unwrap_or_default());
+        self.option_dialog
+            .check_use_aria2
+            .set_checked(config.use_aria2);
+        self.option_dialog
+            .input_aria2_connections
+            .set_value(&config.aria2_connections.to_string());
+        self.option_dialog
+            .check_api_enabled
+            .set_checked(config.api_enabled);
+        self.option_dialog
+            .input_api_port
+            .set_value(&config.api_port.to_string());
+        self.option_dialog
+            .input_api_token
+            .set_value(&config.api_token.unwrap_or_default());
+
+        let mirrors: Vec<String> = config.mirrors.iter().map(|x| x.to_line()).collect();
+        self.option_dialog
+            .input_mirrors
+            .set_value(&mirrors.join("\n"));
+
+        self.option_dialog
+            .check_proxy_enabled
+            .set_checked(config.proxy.enabled);
+        self.option_dialog.input_proxy_url.set_value(&config.proxy.url);
+        self.option_dialog
+            .input_proxy_username
+            .set_value(&config.proxy.username.clone().unwrap_or_default());
+        self.option_dialog
+            .input_proxy_password
+            .set_value(&config.proxy.password.clone().unwrap_or_default());
+        self.option_dialog
+            .input_proxy_bypass
+            .set_value(&config.proxy.bypass.join(", "));
+
+        self.option_dialog
+            .input_max_running
+            .set_value(&config.max_running_tasks.to_string());
+        self.option_dialog
+            .input_run_window
+            .set_value(&config.run_window.unwrap_or_default());
+
+        let rules: Vec<String> = config.rules.iter().map(|x| x.to_line()).collect();
+        self.option_dialog.input_rules.set_value(&rules.join("\n"));
+    }
+
+    fn select_dir(&mut self) {
+        if let Some(dir) = dialog::dir_chooser("Choose default output dir", "", false) {
+            self.option_dialog.input_dir.set_value(&dir);
+        }
+    }
+
+    fn submit(&mut self) {
+        let mut config = get_config();
+
+        let output_dir = self.option_dialog.input_dir.value().trim().to_string();
+        config.output_dir = match output_dir.len() {
+            0 => None,
+            _ => Some(output_dir.clone()),
+        };
+        config.check_engine_update = self.option_dialog.check_engine_update.is_checked();
+        config.watch_clipboard = self.option_dialog.check_watch_clipboard.is_checked();
+        config.skip_archived = self.option_dialog.check_skip_archived.is_checked();
+        config.engine_fallback = self
+            .option_dialog
+            .input_engine_fallback
+            .value()
+            .split(',')
+            .map(|x| x.trim().to_owned())
+            .filter(|x| x.len() > 0)
+            .collect();
+
+        let stream_preference = self.option_dialog.input_stream_preference.value().trim().to_string();
+        config.stream_preference = match stream_preference.len() {
+            0 => None,
+            _ => {
+                if let Err(error) = Preference::parse(&stream_preference) {
+                    dialog::alert_default(&format!("Invalid stream preference: {}", error));
+                    return;
+                }
+                Some(stream_preference)
+            }
+        };
+
+        let github_token = self.option_dialog.input_github_token.value().trim().to_string();
+        config.github_token = match github_token.len() {
+            0 => None,
+            _ => Some(github_token),
+        };
+        config.use_aria2 = self.option_dialog.check_use_aria2.is_checked();
+        match self.option_dialog.input_aria2_connections.value().trim().parse() {
+            Ok(connections) if (1..=16).contains(&connections) => {
+                config.aria2_connections = connections
+            }
+            _ => {
+                dialog::alert_default("aria2 connections should be between 1 and 16");
+                return;
+            }
+        }
+        config.api_enabled = self.option_dialog.check_api_enabled.is_checked();
+        match self.option_dialog.input_api_port.value().trim().parse() {
+            Ok(port) if port > 0 => config.api_port = port,
+            _ => {
+                dialog::alert_default("API port should be between 1 and 65535");
+                return;
+            }
+        }
+        config.proxy.enabled = self.option_dialog.check_proxy_enabled.is_checked();
+        let proxy_url = self.option_dialog.input_proxy_url.value().trim().to_string();
+        config.proxy.url = match (proxy_url.len(), config.proxy.enabled) {
+            (0, false) => proxy_url,
+            _ => match parse_proxy_url(&proxy_url) {
+                Ok(proxy_url) => proxy_url,
+                Err(error) => {
+                    dialog::alert_default(&format!("Invalid proxy url: {}", error));
+                    return;
+                }
+            },
+        };
+        let username = self.option_dialog.input_proxy_username.value().trim().to_string();
+        config.proxy.username = match username.len() {
+            0 => None,
+            _ => Some(username),
+        };
+        let password = self.option_dialog.input_proxy_password.value();
+        config.proxy.password = match password.len() {
+            0 => None,
+            _ => Some(password),
+        };
+        config.proxy.bypass = self
+            .option_dialog
+            .input_proxy_bypass
+            .value()
+            .split(',')
+            .map(|x| x.trim().to_owned())
+            .filter(|x| x.len() > 0)
+            .collect();
+        match self.option_dialog.input_max_running.value().trim().parse() {
+            Ok(max_running) => config.max_running_tasks = max_running,
+            Err(_) => {
+                dialog::alert_default("Max running should be a number, 0 for no limit");
+                return;
+            }
+        }
+        let run_window = self.option_dialog.input_run_window.value().trim().to_string();
+        config.run_window = match run_window.len() {
+            0 => None,
+            _ => match TimeWindow::parse(&run_window) {
+                Ok(window) => Some(window.to_string()),
+                Err(error) => {
+                    dialog::alert_default(&format!("Invalid run window: {}", error));
+                    return;
+                }
+            },
+        };
+        let api_token = self.option_dialog.input_api_token.value().trim().to_string();
+        config.api_token = match api_token.len() {
+            0 => None,
+            _ => Some(api_token),
+        };
+        config.mirrors = self
+            .option_dialog
+            .input_mirrors
+            .value()
+            .lines()
+            .filter_map(Mirror::from_line)
+            .collect();
+        config.rules = self
+            .option_dialog
+            .input_rules
+            .value()
+            .lines()
+            .filter_map(DomainRule::from_line)
+            .collect();
+
+        match set_config(config) {
+            Ok(_) => {
+                if output_dir.len() > 0 {
+                    send_message(AddUrlDialogMessage::SetOutputDir(output_dir));
+                }
+                self.option_dialog.window.hide();
+            }
+            Err(error) => dialog::alert_default(&format!("Failed to save options: {}", error)),
+        }
+    }
+
+    pub fn handle_message(&mut self, message: OptionDialogMessage) {
+        match message {
+            OptionDialogMessage::Show => {
+                self.reset();
+                self.option_dialog.window.show();
+            }
+            OptionDialogMessage::Hide => self.option_dialog.window.hide(),
+            OptionDialogMessage::Submit => self.submit(),
+            OptionDialogMessage::Reset => self.reset(),
+            OptionDialogMessage::SelectDir => self.select_dir(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum OptionDialogMessage {
+    Show,
+    Hide,
+    Submit,
+    Reset,
+    SelectDir,
+}
+
+impl From<OptionDialogMessage> for AppMessage {
+    fn from(value: OptionDialogMessage) -> Self {
+        Self::OptionDialog(value)
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Ordering,
+    rc::Rc,
+};
+
+use fltk_table::SmartTable;
+
+use super::utils::*;
+use crate::downloader::DownloadInfo;
+use fltk::{prelude::*, *};
+
+const COLUMNS: [&str; 12] = [
+    "", "Engine", "Ext", "Quality", "Resolution", "FPS", "Video", "Audio", "Bitrate", "Size",
+    "Duration", "Language",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamFilter {
+    All,
+    /// Streams with both video and audio, or unknown tracks.
+    Muxed,
+    VideoOnly,
+    AudioOnly,
+}
+
+impl StreamFilter {
+    pub const NAMES: [&'static str; 4] = ["All streams", "Video and audio", "Video only", "Audio only"];
+
+    pub fn from_index(index: i32) -> Self {
+        match index {
+            1 => Self::Muxed,
+            2 => Self::VideoOnly,
+            3 => Self::AudioOnly,
+            _ => Self::All,
+        }
+    }
+
+    fn is_match(&self, info: &DownloadInfo) -> bool {
+        match self {
+            Self::All => true,
+            Self::Muxed => info.is_muxed(),
+            Self::VideoOnly => info.is_video_only(),
+            Self::AudioOnly => info.is_audio_only(),
+        }
+    }
+}
+
+/// Stream picker of Add Url dialog.
+///
+/// Click a row to check it, shift-click to check a range, and click a header to sort.
+/// Streams are ordered best first until sorted by a column.
+#[derive(Clone)]
+pub struct StreamTable {
+    table: SmartTable,
+    streams: Rc<RefCell<Vec<(DownloadInfo, bool)>>>,
+    /// Indices of `streams` shown in each row.
+    rows: Rc<RefCell<Vec<usize>>>,
+    sort: Rc<RefCell<Option<(i32, bool)>>>,
+    filter: Rc<Cell<StreamFilter>>,
+    last_row: Rc<Cell<Option<usize>>>,
+}
+
+impl StreamTable {
+    pub fn default() -> Self {
+        let mut table = SmartTable::default();
+        Self::set_table_opts(&mut table);
+
+        let mut result = Self {
+            table,
+            streams: Default::default(),
+            rows: Default::default(),
+            sort: Default::default(),
+            filter: Rc::new(Cell::new(StreamFilter::All)),
+            last_row: Default::default(),
+        };
+
+        let trigger = result.table.trigger();
+        result
+            .table
+            .set_trigger(enums::CallbackTrigger::NotChanged | trigger);
+        result.table.set_callback({
+            let mut stream_table = result.clone();
+            move |tb| {
+                if app::event() != enums::Event::Released {
+                    return;
+                }
+                match tb.callback_context() {
+                    table::TableContext::ColHeader => stream_table.sort_by(tb.callback_col()),
+                    table::TableContext::Cell => {
+                        let is_range = app::event_state().contains(enums::EventState::Shift);
+                        stream_table.toggle(tb.callback_row() as usize, is_range)
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        result
+    }
+
+    pub fn set_streams(&mut self, streams: Vec<DownloadInfo>) {
+        *self.streams.borrow_mut() = streams.into_iter().map(|x| (x, false)).collect();
+        self.last_row.set(None);
+        self.sort();
+        self.update_rows();
+    }
+
+    pub fn clear(&mut self) {
+        self.set_streams(Vec::new());
+    }
+
+    pub fn set_filter(&mut self, filter: StreamFilter) {
+        self.filter.set(filter);
+        self.last_row.set(None);
+        self.update_rows();
+    }
+
+    /// Returns the shown streams in display order.
+    pub fn get_streams(&self) -> Vec<DownloadInfo> {
+        let streams = self.streams.borrow();
+        self.rows
+            .borrow()
+            .iter()
+            .map(|i| streams[*i].0.clone())
+            .collect()
+    }
+
+    /// Returns the checked streams, hidden ones by filter excluded.
+    pub fn get_checked(&self) -> Vec<DownloadInfo> {
+        let streams = self.streams.borrow();
+        self.rows
+            .borrow()
+            .iter()
+            .filter(|i| streams[**i].1)
+            .map(|i| streams[*i].0.clone())
+            .collect()
+    }
+
+    /// Checks the stream shown at `row`.
+    pub fn set_checked(&mut self, row: usize, checked: bool) {
+        if let Some(index) = self.rows.borrow().get(row) {
+            self.streams.borrow_mut()[*index].1 = checked;
+        }
+        self.update_rows();
+    }
+
+    pub fn check_all(&mut self, checked: bool) {
+        let mut streams = self.streams.borrow_mut();
+        for index in self.rows.borrow().iter() {
+            streams[*index].1 = checked;
+        }
+        drop(streams);
+        self.update_rows();
+    }
+
+    fn toggle(&mut self, row: usize, is_range: bool) {
+        let index = match self.rows.borrow().get(row) {
+            Some(index) => *index,
+            None => return,
+        };
+        let checked = !self.streams.borrow()[index].1;
+
+        let (first, last) = match (is_range, self.last_row.get()) {
+            (true, Some(last_row)) => (row.min(last_row), row.max(last_row)),
+            _ => (row, row),
+        };
+        {
+            let rows = self.rows.borrow();
+            let mut streams = self.streams.borrow_mut();
+            for index in rows.iter().take(last + 1).skip(first) {
+                streams[*index].1 = checked;
+            }
+        }
+
+        self.last_row.set(Some(row));
+        self.update_rows();
+    }
+
+    fn sort_by(&mut self, col: i32) {
+        // Sort descending first, so that the best stream comes first
+        let descending = match *self.sort.borrow() {
+            Some((sort_col, descending)) if sort_col == col => !descending,
+            _ => true,
+        };
+        *self.sort.borrow_mut() = Some((col, descending));
+        self.last_row.set(None);
+        self.sort();
+        self.update_rows();
+    }
+
+    fn sort(&mut self) {
+        let mut streams = self.streams.borrow_mut();
+
+        // Best first, ties keep the order of stream ids so that the order is stable
+        streams.sort_by(|(a, _), (b, _)| {
+            compare_best(b, a).then_with(|| a.stream_id.cmp(&b.stream_id))
+        });
+
+        if let Some((col, descending)) = *self.sort.borrow() {
+            streams.sort_by(|(a, a_checked), (b, b_checked)| {
+                let ordering = match col {
+                    0 => a_checked.cmp(b_checked),
+                    _ => compare_stream(a, b, col),
+                };
+                match descending {
+                    true => ordering.reverse(),
+                    false => ordering,
+                }
+            });
+        }
+    }
+
+    fn update_rows(&mut self) {
+        let filter = self.filter.get();
+        *self.rows.borrow_mut() = self
+            .streams
+            .borrow()
+            .iter()
+            .enumerate()
+            .filter(|(_, (info, _))| filter.is_match(info))
+            .map(|(i, _)| i)
+            .collect();
+
+        while self.table.rows() > 0 {
+            self.table.remove_row(self.table.rows() - 1);
+        }
+
+        let streams = self.streams.borrow();
+        for index in self.rows.borrow().iter() {
+            let (info, checked) = &streams[*index];
+            let values = stream_to_row(info, *checked);
+            let values: Vec<&str> = values.iter().map(|x| x.as_str()).collect();
+            self.table.append_row("", &values);
+        }
+
+        self.table.redraw();
+    }
+
+    fn set_table_opts(table: &mut SmartTable) {
+        table.set_opts(fltk_table::TableOpts {
+            rows: 0,
+            cols: COLUMNS.len() as i32,
+            editable: false,
+            cell_border_color: enums::Color::from_rgb(255, 255, 255),
+            cell_align: enums::Align::Left,
+            ..Default::default()
+        });
+        table.set_row_header(false);
+        table.set_row_height_all(20);
+        for (i, name) in COLUMNS.iter().enumerate() {
+            table.set_col_header_value(i as i32, name);
+            table.set_col_width(i as i32, 70);
+        }
+        table.set_col_width(0, 30);
+        table.set_col_width(3, 180);
+    }
+}
+
+widget_extends!(StreamTable, SmartTable, table);
+
+/// Orders streams with both tracks above single track ones, then by quality.
+fn compare_best(a: &DownloadInfo, b: &DownloadInfo) -> Ordering {
+    let rank = |x: &DownloadInfo| {
+        (
+            StreamFilter::Muxed.is_match(x),
+            x.height.unwrap_or(0),
+            x.bitrate.unwrap_or(0),
+            x.stream_size,
+        )
+    };
+    rank(a).cmp(&rank(b))
+}
+
+fn compare_stream(a: &DownloadInfo, b: &DownloadInfo, col: i32) -> Ordering {
+    let compare_f64 = |a: Option<f64>, b: Option<f64>| a.unwrap_or(-1.0).total_cmp(&b.unwrap_or(-1.0));
+    match col {
+        1 => a.downloader.cmp(&b.downloader),
+        2 => a.ext.cmp(&b.ext),
+        3 => a.stream_name.cmp(&b.stream_name),
+        4 => (a.height, a.width).cmp(&(b.height, b.width)),
+        5 => compare_f64(a.fps, b.fps),
+        6 => a.vcodec.cmp(&b.vcodec),
+        7 => a.acodec.cmp(&b.acodec),
+        8 => a.bitrate.cmp(&b.bitrate),
+        9 => a.stream_size.cmp(&b.stream_size),
+        10 => compare_f64(a.duration, b.duration),
+        11 => a.language.cmp(&b.language),
+        _ => Ordering::Equal,
+    }
+}
+
+fn stream_to_row(info: &DownloadInfo, checked: bool) -> Vec<String> {
+    let resolution = match (info.width, info.height) {
+        (Some(width), Some(height)) => format!("{}x{}", width, height),
+        (None, Some(height)) => format!("{}p", height),
+        _ => "".to_owned(),
+    };
+    // Show a missing track as "none", like youtube-dl does
+    let codec = |codec: &Option<String>, has_track: Option<bool>| match (codec, has_track) {
+        (Some(codec), _) => codec.clone(),
+        (None, Some(false)) => "none".to_owned(),
+        _ => "".to_owned(),
+    };
+
+    vec![
+        match checked {
+            true => "[x]".to_owned(),
+            false => "[ ]".to_owned(),
+        },
+        info.downloader.clone(),
+        info.ext.clone(),
+        info.stream_name.clone(),
+        resolution,
+        info.fps.map(|x| format!("{:.0}", x)).unwrap_or_default(),
+        codec(&info.vcodec, info.has_video),
+        codec(&info.acodec, info.has_audio),
+        info.bitrate.map(|x| format!("{} kbps", x)).unwrap_or_default(),
+        size_to_string(info.stream_size),
+        info.duration
+            .map(|x| duration_to_string(x as u64))
+            .unwrap_or_default(),
+        info.language.clone().unwrap_or_default(),
+    ]
+}
@@ -0,0 +1,426 @@
+use std::{collections::HashSet, sync::Arc};
+
+use chrono::TimeZone;
+use fltk::{prelude::*, *};
+use fltk_table::SmartTable;
+
+use crate::{
+    config::{get_config, set_config},
+    downloader::*,
+    send_message,
+    subscription::*,
+    AppMessage,
+};
+
+use super::{MainFormMessage, StatusBar};
+
+mod ui {
+    fl2rust_macro::include_ui!("./src/ui/subscriptions.fl");
+}
+
+#[derive(Clone)]
+pub struct SubscriptionManager {
+    ui: ui::UserInterface,
+    table: SmartTable,
+    subscriptions: Vec<Subscription>,
+    /// Ids of subscriptions being checked.
+    checking: HashSet<String>,
+}
+
+impl SubscriptionManager {
+    pub fn default() -> Self {
+        let mut ui = ui::UserInterface::make_window();
+
+        let mut table = SmartTable::default();
+        Self::set_table_opts(&mut table);
+        table.handle(|tb, event| {
+            if event == enums::Event::Released {
+                let (row_top, _, row_bot, _) = tb.get_selection();
+                tb.set_selection(row_top, 0, row_bot, 6);
+                send_message(SubscriptionMessage::Select);
+            }
+            false
+        });
+        ui.table_parent.add_resizable(&*table);
+        let table = table.size_of_parent().center_of_parent();
+
+        let mut engine_names = vec![AUTO_ENGINE.to_owned()];
+        engine_names.extend(get_engine_names());
+        ui.choice_engine.add_choice(engine_names.join("|").as_str());
+        ui.choice_engine.set_value(0);
+
+        ui.input_interval
+            .set_value(&get_config().subscription_interval.to_string());
+
+        // Due subscriptions are checked every minute, the first time soon after startup
+        app::add_timeout3(10.0, |handle| {
+            send_message(SubscriptionMessage::Tick);
+            app::repeat_timeout3(60.0, handle);
+        });
+
+        let mut result = Self {
+            ui,
+            table,
+            subscriptions: load_subscriptions(),
+            checking: HashSet::new(),
+        };
+
+        result.bind_message();
+        result.update_rows();
+        result
+    }
+
+    fn bind_message(&mut self) {
+        self.ui
+            .btn_select_dir
+            .set_callback(|_| send_message(SubscriptionMessage::SelectDir));
+        self.ui
+            .btn_add
+            .set_callback(|_| send_message(SubscriptionMessage::Add));
+        self.ui
+            .btn_save
+            .set_callback(|_| send_message(SubscriptionMessage::Save));
+        self.ui
+            .btn_remove
+            .set_callback(|_| send_message(SubscriptionMessage::Remove));
+        self.ui
+            .btn_check
+            .set_callback(|_| send_message(SubscriptionMessage::Check));
+        self.ui
+            .btn_check_all
+            .set_callback(|_| send_message(SubscriptionMessage::CheckAll));
+        self.ui
+            .input_interval
+            .set_trigger(enums::CallbackTrigger::Changed);
+        self.ui
+            .input_interval
+            .set_callback(|_| send_message(SubscriptionMessage::IntervalChanged));
+        self.ui
+            .btn_close
+            .set_callback(|_| send_message(SubscriptionMessage::Hide));
+    }
+
+    fn get_selected(&self) -> Vec<usize> {
+        let (row_top, _, row_bot, _) = self.table.get_selection();
+        if row_top < 0 {
+            return Vec::new();
+        }
+        (row_top as usize..=row_bot as usize)
+            .filter(|x| *x < self.subscriptions.len())
+            .collect()
+    }
+
+    /// Fills the form with the first selected subscription.
+    fn select(&mut self) {
+        let subscription = match self.get_selected().first() {
+            Some(i) => self.subscriptions[*i].clone(),
+            None => return,
+        };
+
+        self.ui.input_name.set_value(&subscription.name);
+        self.ui.input_url.set_value(&subscription.url);
+        let index = subscription
+            .engine
+            .as_ref()
+            .map(|x| self.ui.choice_engine.find_index(x))
+            .unwrap_or(0);
+        self.ui.choice_engine.set_value(index.max(0));
+        self.ui
+            .input_preference
+            .set_value(&subscription.preference.unwrap_or_default());
+        self.ui
+            .input_dir
+            .set_value(&subscription.output_dir.unwrap_or_default());
+        self.ui
+            .input_file_name
+            .set_value(&subscription.file_name.unwrap_or_default());
+        self.ui.check_enabled.set_checked(subscription.enabled);
+        self.ui
+            .check_download_existing
+            .set_checked(subscription.download_existing);
+    }
+
+    /// Reads the form into `subscription`, a changed url is subscribed anew.
+    fn read_form(&self, subscription: &mut Subscription) -> Result<(), String> {
+        let url = self.ui.input_url.value().trim().to_owned();
+        if url.len() == 0 {
+            return Err("Url is empty!".to_owned());
+        }
+        let preference = self.ui.input_preference.value().trim().to_owned();
+        if preference.len() > 0 {
+            Preference::parse(&preference).map_err(|x| x.to_string())?;
+        }
+
+        let optional = |value: String| match value.trim().len() {
+            0 => None,
+            _ => Some(value.trim().to_owned()),
+        };
+        if subscription.url != url {
+            subscription.baseline.clear();
+            subscription.last_checked = None;
+            subscription.last_status.clear();
+        }
+        subscription.name = match self.ui.input_name.value().trim() {
+            "" => url.clone(),
+            name => name.to_owned(),
+        };
+        subscription.url = url;
+        subscription.engine = self.ui.choice_engine.choice().filter(|x| x != AUTO_ENGINE);
+        subscription.preference = optional(preference);
+        subscription.output_dir = optional(self.ui.input_dir.value());
+        subscription.file_name = optional(self.ui.input_file_name.value());
+        subscription.enabled = self.ui.check_enabled.is_checked();
+        subscription.download_existing = self.ui.check_download_existing.is_checked();
+        Ok(())
+    }
+
+    fn add(&mut self) {
+        let mut subscription = Subscription::default();
+        if let Err(error) = self.read_form(&mut subscription) {
+            self.ui.set_status_bar_error(&error);
+            return;
+        }
+        if self.subscriptions.iter().any(|x| x.url == subscription.url) {
+            self.ui.set_status_bar_error("Already subscribed!");
+            return;
+        }
+
+        let id = subscription.id.clone();
+        self.subscriptions.push(subscription);
+        self.save(&format!("{} subscription(s)", self.subscriptions.len()));
+        self.check(vec![id]);
+    }
+
+    fn update(&mut self) {
+        let i = match self.get_selected().first() {
+            Some(i) => *i,
+            None => {
+                self.ui
+                    .set_status_bar_error("Please select a subscription first!");
+                return;
+            }
+        };
+
+        let mut subscription = self.subscriptions[i].clone();
+        match self.read_form(&mut subscription) {
+            Ok(_) => {
+                self.subscriptions[i] = subscription;
+                self.save("Subscription saved");
+            }
+            Err(error) => self.ui.set_status_bar_error(&error),
+        }
+    }
+
+    fn remove(&mut self) {
+        let selected = self.get_selected();
+        if selected.len() == 0 {
+            self.ui
+                .set_status_bar_error("Please select subscriptions first!");
+            return;
+        }
+        for i in selected.into_iter().rev() {
+            self.subscriptions.remove(i);
+        }
+        self.save(&format!("{} subscription(s)", self.subscriptions.len()));
+    }
+
+    fn save(&mut self, message: &str) {
+        self.update_rows();
+        match save_subscriptions(&self.subscriptions) {
+            Ok(_) => self.ui.set_status_bar_success(message),
+            Err(error) => self.ui.set_status_bar_error(&error.to_string()),
+        }
+    }
+
+    fn select_dir(&mut self) {
+        if let Some(dir) = dialog::dir_chooser("Choose dir to save download file", "", false) {
+            self.ui.input_dir.set_value(&dir);
+        }
+    }
+
+    fn set_interval(&mut self) {
+        if let Ok(interval) = self.ui.input_interval.value().trim().parse::<u64>() {
+            let mut config = get_config();
+            config.subscription_interval = interval;
+            if let Err(error) = set_config(config) {
+                self.ui.set_status_bar_error(&error.to_string());
+            }
+        }
+    }
+
+    /// Checks subscriptions one after another in background, results come back
+    /// as `Checked` messages.
+    fn check(&mut self, ids: Vec<String>) {
+        let subscriptions: Vec<Subscription> = self
+            .subscriptions
+            .iter()
+            .filter(|x| ids.contains(&x.id) && self.checking.contains(&x.id) == false)
+            .cloned()
+            .collect();
+        if subscriptions.len() == 0 {
+            return;
+        }
+
+        for subscription in &subscriptions {
+            self.checking.insert(subscription.id.clone());
+        }
+        self.update_rows();
+        self.ui.set_status_bar_message(&format!(
+            "Checking {} subscription(s)...",
+            subscriptions.len()
+        ));
+
+        std::thread::spawn(move || {
+            for subscription in subscriptions {
+                let result = check_subscription(&subscription).map_err(|x| x.to_string());
+                send_message(SubscriptionMessage::Checked(subscription.id, result));
+            }
+        });
+    }
+
+    fn check_due(&mut self) {
+        let interval = get_config().subscription_interval;
+        if interval == 0 {
+            return;
+        }
+        let now = chrono::Local::now().timestamp();
+        let ids = self
+            .subscriptions
+            .iter()
+            .filter(|x| x.is_due(now, interval))
+            .map(|x| x.id.clone())
+            .collect();
+        self.check(ids);
+    }
+
+    fn checked(&mut self, id: String, result: Result<CheckResult, String>) {
+        self.checking.remove(&id);
+        let subscription = match self.subscriptions.iter_mut().find(|x| x.id == id) {
+            Some(subscription) => subscription,
+            None => return,
+        };
+
+        subscription.apply(&result);
+        let message = format!("{}: {}", subscription.name, subscription.last_status);
+        if let Ok(result) = result {
+            if result.tasks.len() > 0 {
+                send_message(MainFormMessage::AddTask(Arc::new(result.tasks)));
+            }
+        }
+        self.save(&message);
+    }
+
+    fn update_rows(&mut self) {
+        while self.table.rows() > 0 {
+            self.table.remove_row(self.table.rows() - 1);
+        }
+
+        for subscription in &self.subscriptions {
+            let last_checked = subscription
+                .last_checked
+                .and_then(|x| chrono::Local.timestamp_opt(x, 0).single())
+                .map(|x| x.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or("Never".to_owned());
+            let status = match self.checking.contains(&subscription.id) {
+                true => "Checking...".to_owned(),
+                false => subscription.last_status.clone(),
+            };
+            self.table.append_row(
+                "",
+                &[
+                    &subscription.name,
+                    &subscription.url,
+                    subscription.engine.as_deref().unwrap_or(AUTO_ENGINE),
+                    subscription.preference.as_deref().unwrap_or(""),
+                    if subscription.enabled { "Yes" } else { "No" },
+                    &last_checked,
+                    &status,
+                ],
+            );
+        }
+
+        self.table.redraw();
+    }
+
+    fn set_table_opts(table: &mut SmartTable) {
+        table.set_opts(fltk_table::TableOpts {
+            rows: 0,
+            cols: 7,
+            editable: false,
+            cell_border_color: enums::Color::from_rgb(255, 255, 255),
+            cell_align: enums::Align::Left,
+            ..Default::default()
+        });
+        table.set_row_header(false);
+        table.set_row_height_all(20);
+        table.set_col_header_value(0, "Name");
+        table.set_col_header_value(1, "Url");
+        table.set_col_header_value(2, "Engine");
+        table.set_col_header_value(3, "Preference");
+        table.set_col_header_value(4, "Enabled");
+        table.set_col_header_value(5, "Last Checked");
+        table.set_col_header_value(6, "Status");
+        table.set_col_width(0, 120);
+        table.set_col_width(1, 200);
+        table.set_col_width(4, 60);
+        table.set_col_width(5, 110);
+        table.set_col_width(6, 170);
+    }
+
+    pub fn handle_message(&mut self, message: SubscriptionMessage) {
+        match message {
+            SubscriptionMessage::Show => self.ui.window.show(),
+            SubscriptionMessage::Hide => self.ui.window.hide(),
+            SubscriptionMessage::Select => self.select(),
+            SubscriptionMessage::SelectDir => self.select_dir(),
+            SubscriptionMessage::Add => self.add(),
+            SubscriptionMessage::Save => self.update(),
+            SubscriptionMessage::Remove => self.remove(),
+            SubscriptionMessage::Check => {
+                let ids = self
+                    .get_selected()
+                    .into_iter()
+                    .map(|i| self.subscriptions[i].id.clone())
+                    .collect();
+                self.check(ids)
+            }
+            SubscriptionMessage::CheckAll => {
+                let ids = self.subscriptions.iter().map(|x| x.id.clone()).collect();
+                self.check(ids)
+            }
+            SubscriptionMessage::IntervalChanged => self.set_interval(),
+            SubscriptionMessage::Tick => self.check_due(),
+            SubscriptionMessage::Checked(id, result) => self.checked(id, result),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum SubscriptionMessage {
+    Show,
+    Hide,
+    /// A row of the table is clicked.
+    Select,
+    SelectDir,
+    Add,
+    Save,
+    Remove,
+    Check,
+    CheckAll,
+    IntervalChanged,
+    /// Sent every minute to check due subscriptions.
+    Tick,
+    Checked(String, Result<CheckResult, String>),
+}
+
+impl From<SubscriptionMessage> for AppMessage {
+    fn from(value: SubscriptionMessage) -> Self {
+        AppMessage::Subscription(value)
+    }
+}
+
+impl StatusBar for ui::UserInterface {
+    fn get_status_bar(&self) -> output::Output {
+        self.output_status.clone()
+    }
+}
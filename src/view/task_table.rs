@@ -1,21 +1,28 @@
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::{HashMap, VecDeque},
     io::{BufRead, BufReader},
     rc::Rc,
     sync::{
-        mpsc::{self, Sender},
+        mpsc::{self, Receiver, Sender},
         Arc, Mutex,
     },
     time::Instant,
 };
 
 use fltk_table::SmartTable;
-use regex::Regex;
 use uuid::Uuid;
 
 use super::utils::*;
-use crate::downloader::*;
+use super::MainFormMessage;
+use crate::{
+    archive::add_to_archive,
+    config::{get_config, Config},
+    downloader::*,
+    history::{append_history, HistoryEntry, Outcome},
+    send_message,
+    server::{publish_tasks, TaskSnapshot},
+};
 use fltk::{prelude::*, *};
 
 use anyhow::Result;
@@ -36,6 +43,9 @@ impl TaskTable {
                 enums::Event::Released => {
                     let (row_top, _, row_button, _) = tb.get_selection();
                     tb.set_selection(row_top, 0, row_button, 6);
+                    if app::event_mouse_button() == app::MouseButton::Right {
+                        Self::popup_menu();
+                    }
                 }
                 _ => {}
             }
@@ -47,26 +57,88 @@ impl TaskTable {
         Self { table, task_queue }
     }
 
+    /// Offers speed limits of the selected tasks.
+    fn popup_menu() {
+        let mut limits = vec![None, Some(0)];
+        limits.extend(SPEED_LIMITS.map(Some));
+        let labels: Vec<String> = limits
+            .iter()
+            .map(|x| match x {
+                Some(limit) => format!("Limit to {}", limit_to_string(*limit)),
+                None => "Use Global Speed Limit".to_owned(),
+            })
+            .collect();
+        let labels: Vec<&str> = labels.iter().map(|x| x.as_str()).collect();
+
+        let menu = menu::MenuItem::new(&labels);
+        if let Some(item) = menu.popup(app::event_x(), app::event_y()) {
+            let label = item.label().unwrap_or_default();
+            if let Some(index) = labels.iter().position(|x| *x == label) {
+                send_message(MainFormMessage::SetTaskSpeedLimit(limits[index]));
+            }
+        }
+    }
+
     pub fn update_rows(&mut self) {
+        let now = chrono::Local::now().naive_local();
+        let blocked = self.task_queue.get_blocked(now);
         let mut i = 0;
+        let mut snapshots = Vec::new();
         for uuid in self.task_queue.order.clone().borrow().iter() {
             if let Ok(task) = self.task_queue.get_task(*uuid) {
                 let task = task.lock().unwrap();
+                snapshots.push(task.to_snapshot());
+                let status = task.get_status_text(now, blocked.as_deref());
+                if task.download_info.is_live {
+                    // Live recordings have no total size, show what is recorded so far
+                    self.set_task_row(
+                        i,
+                        &task.download_info.title,
+                        &task.download_info.ext,
+                        task.task_info.bytes as usize,
+                        &duration_to_string(task.task_info.get_elapsed()),
+                        0,
+                        task.get_speed(),
+                        &status,
+                    );
+                    i = i + 1;
+                    continue;
+                }
+                let progress = match task.download_info.file_count {
+                    0 => percent_to_string(task.task_info.progress),
+                    file_count => format!("{}/{} files", task.task_info.files, file_count),
+                };
                 self.set_task_row(
                     i,
                     &task.download_info.title,
                     &task.download_info.ext,
                     task.download_info.stream_size,
-                    task.task_info.progress,
+                    &progress,
                     task.task_info.eta,
-                    (task.task_info.speed * task.download_info.stream_size as f64) as usize,
-                    task.task_status,
+                    task.get_speed(),
+                    &status,
                 );
                 i = i + 1;
             }
         }
 
         self.table.redraw();
+        publish_tasks(snapshots);
+    }
+
+    /// Adds a task with an id chosen by caller, so that it can be found later.
+    pub fn add_task_with_id(&mut self, uuid: Uuid, download_info: &DownloadInfo) {
+        self.task_queue.add_task_with_id(uuid, download_info);
+        self.update_rows();
+    }
+
+    /// Adds tasks with ids chosen by caller, without starting them.
+    pub fn add_tasks_with_ids(&mut self, tasks: &Vec<(Uuid, DownloadInfo)>) -> usize {
+        for (uuid, download_info) in tasks {
+            self.task_queue.add_task_with_id(*uuid, download_info);
+        }
+        self.update_rows();
+        tasks.len()
     }
 
     pub fn add_tasks(&mut self, download_info_vec: &Vec<DownloadInfo>) -> usize {
@@ -113,26 +185,55 @@ impl TaskTable {
         self.table.unset_selection();
     }
 
-    pub fn start_select(&mut self) -> Result<usize> {
-        let uuid_vec = self.get_select_uuid();
-        let length = uuid_vec.len();
+    pub fn start_tasks(&mut self, uuid_vec: &Vec<Uuid>) -> Result<()> {
         for uuid in uuid_vec {
-            self.task_queue.start_task(uuid)?;
+            self.task_queue.start_task(*uuid)?;
         }
         self.update_rows();
-        self.table.unset_selection();
-        Ok(length)
+        Ok(())
     }
 
-    pub fn stop_select(&mut self) -> Result<usize> {
-        let uuid_vec = self.get_select_uuid();
-        let length = uuid_vec.len();
+    pub fn stop_tasks(&mut self, uuid_vec: &Vec<Uuid>) -> Result<()> {
         for uuid in uuid_vec {
-            self.task_queue.kill_task(uuid)?;
+            self.task_queue.kill_task(*uuid)?;
         }
         self.update_rows();
+        Ok(())
+    }
+
+    pub fn set_speed_limit_select(&mut self, limit: Option<u64>) -> Result<usize> {
+        let uuid_vec = self.get_select_uuid();
+        for uuid in &uuid_vec {
+            self.task_queue.set_speed_limit(*uuid, limit)?;
+        }
+        self.update_rows();
+        Ok(uuid_vec.len())
+    }
+
+    /// Starts waiting tasks and pauses those outside their windows, every second.
+    pub fn schedule(&mut self) {
+        self.task_queue.schedule();
+    }
+
+    /// Pauses all running tasks until resumed.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.task_queue.paused.set(paused);
+        self.task_queue.schedule();
+        self.update_rows();
+    }
+
+    pub fn start_select(&mut self) -> Result<usize> {
+        let uuid_vec = self.get_select_uuid();
+        self.start_tasks(&uuid_vec)?;
         self.table.unset_selection();
-        Ok(length)
+        Ok(uuid_vec.len())
+    }
+
+    pub fn stop_select(&mut self) -> Result<usize> {
+        let uuid_vec = self.get_select_uuid();
+        self.stop_tasks(&uuid_vec)?;
+        self.table.unset_selection();
+        Ok(uuid_vec.len())
     }
 
     fn set_task_row(
@@ -141,19 +242,18 @@ impl TaskTable {
         title: &str,
         extension: &str,
         size: usize,
-        percent: f64,
+        progress: &str,
         eta: usize,
         speed: usize,
-        status: TaskStatus,
+        status: &str,
     ) {
         self.table.set_cell_value(row, 0, title);
         self.table.set_cell_value(row, 1, extension);
         self.table.set_cell_value(row, 2, &size_to_string(size));
-        self.table
-            .set_cell_value(row, 3, &percent_to_string(percent));
+        self.table.set_cell_value(row, 3, progress);
         self.table.set_cell_value(row, 4, &eta_to_string(eta));
         self.table.set_cell_value(row, 5, &speed_to_string(speed));
-        self.table.set_cell_value(row, 6, &status.to_string());
+        self.table.set_cell_value(row, 6, status);
     }
 
     fn set_table_opts(table: &mut SmartTable) {
@@ -184,30 +284,109 @@ pub enum TaskStatus {
     Queued,
     Running,
     Stopped,
+    Finished,
+    Failed,
+    /// Started, waiting for a free slot or its time window.
+    Waiting,
 }
 
 impl TaskStatus {
     fn to_string(&self) -> String {
         match self {
             Self::Queued => "Queued".to_string(),
+            Self::Waiting => "Waiting".to_string(),
             Self::Running => "Running".to_string(),
             Self::Stopped => "Stopped".to_string(),
+            Self::Finished => "Finished".to_string(),
+            Self::Failed => "Failed".to_string(),
         }
     }
 }
 
 struct Task {
-    _uuid: Uuid,
+    uuid: Uuid,
     download_info: DownloadInfo,
     task_status: TaskStatus,
     task_killer: Option<Sender<bool>>,
     task_info: TaskInfo,
+    /// Bumped on each run, so that a paused run ending late leaves the next alone.
+    run_id: usize,
+}
+
+impl Task {
+    fn get_speed(&self) -> usize {
+        match self.task_info.byte_speed > 0.0 {
+            true => self.task_info.byte_speed as usize,
+            false => (self.task_info.speed * self.download_info.stream_size as f64) as usize,
+        }
+    }
+
+    fn to_snapshot(&self) -> TaskSnapshot {
+        TaskSnapshot {
+            id: self.uuid.to_string(),
+            title: self.download_info.title.clone(),
+            url: self.download_info.url.clone(),
+            engine: self.download_info.downloader.clone(),
+            stream: self.download_info.stream_name.clone(),
+            ext: self.download_info.ext.clone(),
+            size: self.get_size(),
+            progress: self.task_info.progress,
+            bytes: self.task_info.bytes,
+            speed: self.get_speed(),
+            eta: self.task_info.eta,
+            status: self.task_status.to_string(),
+            output: self.download_info.get_output(),
+        }
+    }
+
+    fn get_size(&self) -> usize {
+        match (self.download_info.stream_size, self.task_info.total_bytes) {
+            (0, 0) => self.task_info.bytes as usize,
+            (0, total_bytes) => total_bytes as usize,
+            (size, _) => size,
+        }
+    }
+
+    /// Tells what a waiting task waits for, `blocked` being the reason of all tasks.
+    fn get_status_text(&self, now: chrono::NaiveDateTime, blocked: Option<&str>) -> String {
+        if let (TaskStatus::Running, Some(limit)) =
+            (self.task_status, self.download_info.speed_limit)
+        {
+            return format!("Running ({})", limit_to_string(limit));
+        }
+        if self.task_status != TaskStatus::Waiting {
+            return self.task_status.to_string();
+        }
+        match (self.download_info.schedule.as_ref(), blocked) {
+            (Some(schedule), _) if schedule.can_run(now) == false => schedule.to_string(now),
+            (_, Some(blocked)) => blocked.to_owned(),
+            _ => self.task_status.to_string(),
+        }
+    }
+
+    fn to_history(&self, started_at: i64, error: Option<String>) -> HistoryEntry {
+        let outcome = match self.task_status {
+            TaskStatus::Finished => Outcome::Finished,
+            TaskStatus::Failed => Outcome::Failed,
+            _ => Outcome::Stopped,
+        };
+        HistoryEntry {
+            size: self.get_size() as u64,
+            ..HistoryEntry::new(&self.download_info, started_at, outcome, error)
+        }
+    }
 }
 
 struct TaskInfo {
     progress: f64,
     speed: f64,
     eta: usize,
+    files: usize,
+    bytes: u64,
+    byte_speed: f64,
+    /// Total bytes of transfers, whose stream size is unknown before downloading.
+    total_bytes: u64,
+    started: Option<Instant>,
 }
 
 impl TaskInfo {
@@ -216,6 +395,40 @@ impl TaskInfo {
             progress: 0.0,
             speed: 0.0,
             eta: 60 * 60 * 24,
+            files: 0,
+            bytes: 0,
+            byte_speed: 0.0,
+            total_bytes: 0,
+            started: None,
+        }
+    }
+
+    pub fn get_elapsed(&self) -> u64 {
+        self.started.map(|x| x.elapsed().as_secs()).unwrap_or(0)
+    }
+
+    pub fn update_bytes(&mut self, bytes: u64, dur: f64) {
+        if bytes > self.bytes && dur > 0.0 {
+            self.byte_speed = (bytes - self.bytes) as f64 / dur;
+        }
+        self.bytes = bytes;
+    }
+
+    pub fn update_file(&mut self, file_count: usize, dur: f64) {
+        self.files = self.files + 1;
+        if file_count > 0 {
+            self.update(self.files.min(file_count) as f64 / file_count as f64, dur);
+        }
+    }
+
+    pub fn update_transfer(&mut self, completed: u64, total: u64, speed: u64) {
+        if total > 0 {
+            self.progress = completed as f64 / total as f64;
+        }
+        self.total_bytes = total;
+        self.byte_speed = speed as f64;
+        if speed > 0 {
+            self.eta = (total.saturating_sub(completed) / speed) as usize;
         }
     }
 
@@ -238,89 +451,192 @@ impl TaskInfo {
 struct TaskQueue {
     inner: Rc<RefCell<HashMap<Uuid, Arc<Mutex<Task>>>>>,
     order: Rc<RefCell<VecDeque<Uuid>>>,
+    /// Paused by hand, all tasks wait until resumed.
+    paused: Rc<Cell<bool>>,
 }
 
 impl TaskQueue {
+    /// Queues the task for scheduler, which starts it when allowed.
     fn start_task(&self, uuid: Uuid) -> Result<()> {
         let task = self.get_task(uuid)?;
+        let mut task = task.lock().unwrap();
+        if task.task_status != TaskStatus::Running {
+            task.task_status = TaskStatus::Waiting;
+        }
+        drop(task);
+
+        self.schedule();
+        Ok(())
+    }
+
+    /// Gets why no task may run now, if paused or outside run window of options.
+    fn get_blocked(&self, now: chrono::NaiveDateTime) -> Option<String> {
+        if self.paused.get() {
+            return Some("Paused".to_owned());
+        }
+        get_config()
+            .run_window
+            .and_then(|x| TimeWindow::parse(&x).ok())
+            .filter(|x| x.contains(now.time()) == false)
+            .map(|x| format!("Runs {}", x.to_string()))
+    }
+
+    /// Pauses running tasks which may not run now, then starts waiting ones in
+    /// order until `max_running_tasks` are running.
+    fn schedule(&self) {
+        let now = chrono::Local::now().naive_local();
+        let blocked = self.get_blocked(now).is_some();
+        let can_run = |task: &Task| {
+            blocked == false
+                && task
+                    .download_info
+                    .schedule
+                    .as_ref()
+                    .map(|x| x.can_run(now))
+                    .unwrap_or(true)
+        };
 
-        if TaskStatus::Running == {
+        let tasks: Vec<(Uuid, Arc<Mutex<Task>>)> = self
+            .order
+            .borrow()
+            .iter()
+            .filter_map(|x| self.get_task(*x).ok().map(|task| (*x, task)))
+            .collect();
+
+        let mut running = 0;
+        for (_, task) in &tasks {
+            let mut task = task.lock().unwrap();
+            if task.task_status != TaskStatus::Running {
+                continue;
+            }
+            match can_run(&task) {
+                true => running = running + 1,
+                false => {
+                    task.task_status = TaskStatus::Waiting;
+                    if let Some(sender) = task.task_killer.take() {
+                        let _ = sender.send(true);
+                    }
+                }
+            }
+        }
+
+        let config = get_config();
+        let max_running = config.max_running_tasks;
+        let mut starting = Vec::new();
+        for (uuid, task) in &tasks {
+            if max_running > 0 && running + starting.len() >= max_running {
+                break;
+            }
             let task = task.lock().unwrap();
-            task.task_status
-        } {
-            return Ok(());
+            if task.task_status == TaskStatus::Waiting && can_run(&task) {
+                starting.push(*uuid);
+            }
+        }
+
+        // Engines and aria2c took their share of global cap on start, so they
+        // restart to take the new one, and get started again on next round
+        let count = running + starting.len();
+        if count != get_running_count() {
+            set_running_count(count);
+            if get_global_limit().is_some() {
+                for (_, task) in &tasks {
+                    let mut task = task.lock().unwrap();
+                    if task.task_status == TaskStatus::Running && uses_global_share(&task, &config) {
+                        task.task_status = TaskStatus::Waiting;
+                        if let Some(sender) = task.task_killer.take() {
+                            let _ = sender.send(true);
+                        }
+                    }
+                }
+            }
+        }
+
+        for uuid in starting {
+            self.run_task(uuid);
         }
+    }
+
+    fn run_task(&self, uuid: Uuid) {
+        let task = match self.get_task(uuid) {
+            Ok(task) => task,
+            Err(_) => return,
+        };
 
         let (sender, receiver) = mpsc::channel::<bool>();
-        {
+        let run_id = {
             let mut task = task.lock().unwrap();
             task.task_killer = Some(sender.clone());
-        }
+            task.task_status = TaskStatus::Running;
+            task.run_id = task.run_id + 1;
+            task.run_id
+        };
 
         std::thread::spawn({
             move || {
-                match {
-                    let mut task = task.lock().unwrap();
-                    task.task_status = TaskStatus::Running;
-                    execute_download_info(&task.download_info)
-                } {
-                    Ok((mut child, cookie_file, read_stderr)) => {
-                        let mut reader: Box<dyn BufRead> = {
-                            if read_stderr {
-                                Box::new(BufReader::new(child.stderr.take().unwrap()))
-                            } else {
-                                Box::new(BufReader::new(child.stdout.take().unwrap()))
-                            }
-                        };
-
-                        let mut buf = Vec::new();
-                        let mut before = Instant::now();
-                        let re: Regex = Regex::new(r"(?<progress>[0-9\.]*?)%").unwrap();
-                        while let Ok(length) = reader.read_until(b'%', &mut buf) {
-                            if let Ok(should_kill) = receiver.try_recv() {
-                                if should_kill {
-                                    let _ = child.kill();
-                                    let mut task = task.lock().unwrap();
-                                    task.task_status = TaskStatus::Stopped;
-                                    break;
-                                }
-                            }
-                            match length {
-                                0 => break,
-                                _ => {
-                                    let result = String::from_utf8_lossy(&buf);
-                                    let result = result.trim();
-                                    if let Some(caps) = re.captures(result) {
-                                        let progress = caps.name("progress").unwrap();
-                                        let progress =
-                                            progress.as_str().parse::<f64>().unwrap_or(-1.0)
-                                                / 100.0;
-
-                                        let now = Instant::now();
-                                        let dur = now - before;
-                                        before = now;
-
-                                        let mut task = task.lock().unwrap();
-                                        task.task_info.update(progress, dur.as_secs_f64());
-                                    }
-                                    buf.clear();
-                                }
-                            }
-                        }
+                let config = get_config();
+                let use_parts = {
+                    let task = task.lock().unwrap();
+                    let info = &task.download_info;
+                    info.parts.len() > 0 && (config.use_aria2 || info.downloader == DIRECT_ENGINE)
+                };
+
+                let started_at = chrono::Local::now().timestamp();
+                task.lock().unwrap().task_info.started = Some(Instant::now());
+
+                let result = match use_parts {
+                    true => {
+                        let connections = config.use_aria2.then_some(config.aria2_connections);
+                        run_parts_task(&task, &receiver, connections)
+                    }
+                    false => run_engine_task(&task, &receiver),
+                };
 
-                        if let Some(cookie_file) = cookie_file {
-                            let _ = std::fs::remove_file(cookie_file);
+                let mut task = task.lock().unwrap();
+                if task.run_id != run_id {
+                    return;
+                }
+                let error = match result {
+                    Ok(_) => {
+                        task.task_status = TaskStatus::Finished;
+                        if let Err(error) = add_to_archive(&task.download_info) {
+                            println!("{}", error)
                         }
+                        None
                     }
+                    // Stopped by hand, or paused by scheduler
+                    Err(_) if task.task_status != TaskStatus::Running => None,
                     Err(error) => {
-                        println!("{}", error)
+                        println!("{}", error);
+                        task.task_status = TaskStatus::Failed;
+                        Some(error.to_string())
                     }
+                };
+                // A paused task is not over yet
+                if task.task_status == TaskStatus::Waiting {
+                    return;
+                }
+                if let Err(error) = append_history(&task.to_history(started_at, error)) {
+                    println!("{}", error)
                 }
-
-                task.lock().unwrap().task_status = TaskStatus::Stopped;
             }
         });
+    }
+
+    /// Running tasks are restarted by scheduler, as engines take the limit on start only.
+    fn set_speed_limit(&self, uuid: Uuid, limit: Option<u64>) -> Result<()> {
+        let task = self.get_task(uuid)?;
+        let mut task = task.lock().unwrap();
+        if task.download_info.speed_limit == limit {
+            return Ok(());
+        }
 
+        task.download_info.speed_limit = limit;
+        if task.task_status == TaskStatus::Running {
+            task.task_status = TaskStatus::Waiting;
+            if let Some(sender) = task.task_killer.take() {
+                sender.send(true)?;
+            }
+        }
         Ok(())
     }
 
@@ -328,6 +644,9 @@ impl TaskQueue {
         let task = self.get_task(uuid)?;
         let mut task = task.lock().unwrap();
 
+        if matches!(task.task_status, TaskStatus::Running | TaskStatus::Waiting) {
+            task.task_status = TaskStatus::Stopped;
+        }
         if let Some(sender) = task.task_killer.take() {
             sender.send(true)?;
         }
@@ -336,14 +655,21 @@ impl TaskQueue {
     }
 
     fn add_task(&self, download_info: &DownloadInfo) {
-        let uuid = Uuid::new_v4();
+        self.add_task_with_id(Uuid::new_v4(), download_info);
+    }
 
+    fn add_task_with_id(&self, uuid: Uuid, download_info: &DownloadInfo) {
         let task = Task {
-            _uuid: uuid,
+            uuid,
             download_info: download_info.to_owned(),
-            task_status: TaskStatus::Queued,
+            // Scheduled tasks start by themselves
+            task_status: match download_info.schedule {
+                Some(_) => TaskStatus::Waiting,
+                None => TaskStatus::Queued,
+            },
             task_killer: None,
             task_info: TaskInfo::default(),
+            run_id: 0,
         };
 
         self.inner
@@ -374,3 +700,153 @@ impl TaskQueue {
         Ok(task)
     }
 }
+
+/// Tells if the task runs an engine or aria2c limited by a share of global cap, unlike
+/// native downloads taking from global cap directly.
+fn uses_global_share(task: &Task, config: &Config) -> bool {
+    let info = &task.download_info;
+    let is_native =
+        info.parts.len() > 0 && config.use_aria2 == false && info.downloader == DIRECT_ENGINE;
+    info.speed_limit.is_none() && is_native == false
+}
+
+/// Downloads the parts with aria2c when `connections` is given, or over plain HTTP
+/// otherwise, until done or killed.
+fn run_parts_task(
+    task: &Arc<Mutex<Task>>,
+    receiver: &Receiver<bool>,
+    connections: Option<u32>,
+) -> Result<()> {
+    let download_info = task.lock().unwrap().download_info.clone();
+
+    let on_progress = |completed, total, speed| {
+        if let Ok(true) = receiver.try_recv() {
+            return false;
+        }
+        let mut task = task.lock().unwrap();
+        task.task_info.update_transfer(completed, total, speed);
+        true
+    };
+    match connections {
+        Some(connections) => download_parts(&download_info, connections, on_progress)?,
+        None => download_parts_direct(&download_info, on_progress)?,
+    };
+
+    let mut task = task.lock().unwrap();
+    task.task_info.update(1.0, 0.0);
+    task.task_info.byte_speed = 0.0;
+    Ok(())
+}
+
+/// Runs the engine, parsing its progress output until it exits or is killed.
+fn run_engine_task(task: &Arc<Mutex<Task>>, receiver: &Receiver<bool>) -> Result<()> {
+    let download_info = task.lock().unwrap().download_info.clone();
+    let (mut child, cookie_file, engine) = execute_download_info(&download_info)?;
+
+    let deadline = download_info
+        .record_limit
+        .as_ref()
+        .and_then(|x| x.get_remaining())
+        .map(|x| Instant::now() + x);
+
+    let mut reader: Box<dyn BufRead> = {
+        if engine.is_stderr_output() {
+            Box::new(BufReader::new(child.stderr.take().unwrap()))
+        } else {
+            Box::new(BufReader::new(child.stdout.take().unwrap()))
+        }
+    };
+
+    let mut buf = Vec::new();
+    let mut before = Instant::now();
+    let mut killed = false;
+    let mut recorded = false;
+    let mut failed = None;
+    while let Ok(length) = read_progress_line(&mut reader, &mut buf) {
+        if let Ok(true) = receiver.try_recv() {
+            let _ = child.kill();
+            killed = true;
+            break;
+        }
+        if deadline.map(|x| Instant::now() >= x).unwrap_or(false) {
+            let _ = child.kill();
+            recorded = true;
+            break;
+        }
+        match length {
+            0 => break,
+            _ => {
+                let result = String::from_utf8_lossy(&buf);
+                if let Some(progress) = engine.parse_progress(result.trim()) {
+                    if let Progress::Failed(message) = progress {
+                        let _ = child.kill();
+                        failed = Some(message);
+                        break;
+                    }
+                    let now = Instant::now();
+                    let dur = (now - before).as_secs_f64();
+                    before = now;
+
+                    let mut task = task.lock().unwrap();
+                    match progress {
+                        Progress::Percent(progress) => task.task_info.update(progress, dur),
+                        Progress::File => {
+                            let file_count = task.download_info.file_count;
+                            task.task_info.update_file(file_count, dur)
+                        }
+                        Progress::Bytes(bytes) => task.task_info.update_bytes(bytes, dur),
+                        Progress::Transfer {
+                            completed,
+                            total,
+                            speed,
+                        } => task.task_info.update_transfer(completed, total, speed),
+                        Progress::Failed(_) => {}
+                    }
+                }
+                buf.clear();
+            }
+        }
+    }
+
+    let status = child.wait();
+    if let Some(cookie_file) = cookie_file {
+        let _ = std::fs::remove_file(cookie_file);
+    }
+
+    if let Some(message) = failed {
+        return Err(anyhow::anyhow!("{}: {}", download_info.downloader, message));
+    }
+
+    // Recordings reaching their limit are complete
+    match (killed, recorded, status?) {
+        (true, ..) => Err(anyhow::anyhow!("Download stopped")),
+        (_, true, _) => Ok(()),
+        (_, _, status) if status.success() => Ok(()),
+        (_, _, status) => Err(anyhow::anyhow!("{} exited with {}", download_info.downloader, status)),
+    }
+}
+
+/// Reads until `\r` or `\n`, as progress bars of engines redraw themselves with `\r`.
+fn read_progress_line<R: BufRead + ?Sized>(reader: &mut R, buf: &mut Vec<u8>) -> std::io::Result<usize> {
+    let mut read = 0;
+    loop {
+        let (done, used) = {
+            let available = reader.fill_buf()?;
+            match available.iter().position(|x| *x == b'\r' || *x == b'\n') {
+                Some(i) => {
+                    buf.extend_from_slice(&available[..=i]);
+                    (true, i + 1)
+                }
+                None => {
+                    buf.extend_from_slice(available);
+                    (available.len() == 0, available.len())
+                }
+            }
+        };
+        reader.consume(used);
+        read = read + used;
+        if done {
+            return Ok(read);
+        }
+    }
+}
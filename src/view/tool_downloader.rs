@@ -7,10 +7,15 @@ use std::{
 use anyhow::Result;
 use fltk::prelude::*;
 
-use crate::{downloader::get_plugin_dir, send_message, AppMessage};
+use crate::{
+    downloader::{get_plugin_version_dir, http_get, Throttle},
+    send_message, AppMessage,
+};
 
 use super::{
-    utils::{extract_file_to_plugin, percent_to_string, size_to_string, speed_to_string},
+    utils::{
+        extract_file_to_plugin, percent_to_string, set_executable, size_to_string, speed_to_string,
+    },
     EngineManagerMessage,
 };
 
@@ -23,7 +28,14 @@ pub struct Task {
     pub url: String,
     pub output_path: String,
     pub message: String,
-    pub to_plugin: bool,
+    pub plugin: Option<PluginTarget>,
+}
+
+/// The engine release a downloaded asset is installed as.
+#[derive(Clone)]
+pub struct PluginTarget {
+    pub program: String,
+    pub version: String,
 }
 
 #[derive(Clone)]
@@ -79,8 +91,8 @@ impl ToolDownloader {
                     &message, 0, 1.0, 0.0,
                 )));
                 send_message(ToolDownloaderMessage::Hide);
-                if task.to_plugin {
-                    put_to_plugin(&output_path)?;
+                if let Some(plugin) = &task.plugin {
+                    put_to_plugin(&output_path, plugin)?;
                 }
                 return Ok(());
             }
@@ -90,7 +102,7 @@ impl ToolDownloader {
                 start_bytes = std::fs::metadata(&temp_path)?.len() as usize;
             }
 
-            let mut response = ureq::get(&url).call()?;
+            let mut response = http_get(&url).call()?;
 
             let content_length = response
                 .header("Content-Length")
@@ -98,7 +110,7 @@ impl ToolDownloader {
                 .unwrap_or(0);
 
             if start_bytes > 0 || start_bytes < content_length {
-                response = ureq::get(&url)
+                response = http_get(&url)
                     .set("Range", &format!("bytes={}-", start_bytes))
                     .call()?;
             }
@@ -112,13 +124,16 @@ impl ToolDownloader {
 
             let mut response = BufReader::new(response.into_reader());
             let mut buf = [0; 1024 * 1024];
+            let mut throttle = Throttle::new(None);
 
             let mut before = std::time::Instant::now();
 
             loop {
-                let length = response.read(&mut buf)?;
+                let chunk = throttle.get_chunk_size(buf.len());
+                let length = response.read(&mut buf[..chunk])?;
                 output_file.write(&buf[0..length])?;
                 write_length = write_length + length;
+                throttle.consume(length as u64);
 
                 let end = std::time::Instant::now();
                 let duration = end - before;
@@ -144,8 +159,8 @@ impl ToolDownloader {
                     )));
                     std::fs::rename(&temp_path, &output_path)?;
                     send_message(ToolDownloaderMessage::Hide);
-                    if task.to_plugin {
-                        put_to_plugin(&output_path)?;
+                    if let Some(plugin) = &task.plugin {
+                        put_to_plugin(&output_path, plugin)?;
                     }
                     break;
                 }
@@ -224,16 +239,30 @@ impl From<ToolDownloaderMessage> for AppMessage {
     }
 }
 
-fn put_to_plugin(output_path: &PathBuf) -> Result<()> {
+fn put_to_plugin(output_path: &PathBuf, plugin: &PluginTarget) -> Result<()> {
     let file_name = output_path
         .file_name()
         .map(|x| x.to_string_lossy().to_string())
         .ok_or_else(|| anyhow::anyhow!("Unknown filename"))?;
 
     if file_name.ends_with(".zip") {
-        extract_file_to_plugin(output_path)?;
+        extract_file_to_plugin(output_path, &plugin.program, &plugin.version)?;
     } else if file_name.ends_with(".exe") {
-        std::fs::copy(output_path, get_plugin_dir()?.join(&file_name))?;
+        let plugin_dir = get_plugin_version_dir(&plugin.program, &plugin.version)?;
+        std::fs::create_dir_all(&plugin_dir)?;
+        std::fs::copy(output_path, plugin_dir.join(&file_name))?;
+    } else if output_path.extension().is_none() {
+        // Bare executables like `yt-dlp_linux` are stored under the program name
+        let plugin_dir = get_plugin_version_dir(&plugin.program, &plugin.version)?;
+        std::fs::create_dir_all(&plugin_dir)?;
+        let plugin_path = plugin_dir.join(&plugin.program);
+        std::fs::copy(output_path, &plugin_path)?;
+        set_executable(&plugin_path)?;
+    } else {
+        return Err(anyhow::anyhow!(
+            "{} can't be installed, only zip archives and executables are supported",
+            file_name
+        ));
     }
     
     // fltk::dialog::message_default(&format!("{} is downloaded and extract to plugin.", &file_name));
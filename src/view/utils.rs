@@ -15,10 +15,38 @@ pub fn speed_to_string(size: usize) -> String {
     }
 }
 
+/// Speed limit presets in bytes per second, offered in status bar and task menu.
+pub const SPEED_LIMITS: [u64; 6] = [256_000, 512_000, 1_000_000, 2_000_000, 5_000_000, 10_000_000];
+
+/// Formats a speed limit for menus, e.g. `512 KB/s`, 0 for unlimited.
+pub fn limit_to_string(limit: u64) -> String {
+    match limit {
+        0 => "Unlimited".to_owned(),
+        mb if mb >= 1000 * 1000 => format!("{} MB/s", mb as f64 / 1000000.0),
+        kb => format!("{} KB/s", kb as f64 / 1000.0),
+    }
+}
+
 pub fn percent_to_string(percent: f64) -> String {
     format!("{:.1}%", percent * 100.0)
 }
 
+/// Formats elapsed seconds as `HH:MM:SS`.
+pub fn duration_to_string(secs: u64) -> String {
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Opens a file or folder with the default application of the system.
+pub fn open_path(path: &std::path::Path) -> anyhow::Result<()> {
+    let program = match std::env::consts::OS {
+        "windows" => "explorer",
+        "macos" => "open",
+        _ => "xdg-open",
+    };
+    std::process::Command::new(program).arg(path).spawn()?;
+    Ok(())
+}
+
 pub fn eta_to_string(eta: usize) -> String {
     let days = eta / (60 * 60 * 24);
     let hours = (eta - days * 60 * 60 * 24) / (60 * 60);
@@ -34,10 +62,14 @@ pub fn eta_to_string(eta: usize) -> String {
 }
 
 use anyhow::Result;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::PathBuf};
 use url::Url;
 
-
+use crate::{
+    config::{get_app_dir, get_config, Mirror},
+    downloader::{get_manifest, http_get, GithubSource},
+};
 
 #[allow(unused)]
 #[derive(Clone)]
@@ -50,70 +82,111 @@ pub struct GithubLatestRelease {
 
 impl GithubLatestRelease {
     pub fn get_from_github_api(engine_name: &str) -> Option<Self> {
-        match engine_name.to_lowercase().trim() {
-            "lux" => get_lux().ok(),
-            "youtube-dl" => get_youtubedl().ok(),
-            "you-get" => get_youget().ok(),
-            _ => None,
-        }
+        let github = get_manifest(engine_name)?.github?;
+        get_latest_release(&github).ok()
     }
 }
 
+/// Returns the pip package of engines which are installed into a managed venv.
+pub fn get_pip_package(engine_name: &str) -> Option<String> {
+    get_manifest(engine_name)?.pip_package
+}
+
 pub fn get_github_latest(owner: &str, repo: &str) -> Result<serde_json::Value> {
     // https://docs.github.com/en/free-pro-team@latest/rest/releases/releases?apiVersion=2022-11-28#get-the-latest-release
-    let url = format!("https://api.github.com/repos/{owner}/{repo}/releases/latest");
-    let response: serde_json::Value = ureq::get(&url).call()?.into_json()?;
-    Ok(response)
-}
+    let config = get_config();
+    let api_base = config
+        .get_mirror()
+        .and_then(|x| x.api_base.clone())
+        .unwrap_or("https://api.github.com".to_owned());
+    let url = format!(
+        "{}/repos/{owner}/{repo}/releases/latest",
+        api_base.trim_end_matches('/')
+    );
+
+    let cache_file = get_release_cache_file(&url);
+    let cached: Option<ReleaseCache> = cache_file
+        .as_ref()
+        .ok()
+        .and_then(|x| std::fs::read_to_string(x).ok())
+        .and_then(|x| serde_json::from_str(&x).ok());
+
+    let mut request = http_get(&url);
+    if let Some(token) = config.github_token.filter(|x| x.trim().len() > 0) {
+        request = request.set("Authorization", &format!("Bearer {}", token.trim()));
+    }
+    if let Some(cached) = &cached {
+        request = request.set("If-None-Match", &cached.etag);
+    }
+
+    let response = match request.call() {
+        Ok(response) => response,
+        // Fall back to the cached release when rate limited or offline
+        Err(error) => return cached.map(|x| x.body).ok_or(error.into()),
+    };
 
-pub fn replace_github_download(origin: &str, mirror: &str) -> String {
-    match mirror.to_ascii_lowercase().trim() {
-        "kgithub" | "kgithub.com" => origin.replace("https://github.com", "https://kgithub.com"),
-        "ghproxy" | "ghproxy.com" => {
-            origin.replace("https://github.com", "https://ghproxy.com/github.com")
+    if response.status() == 304 {
+        if let Some(cached) = cached {
+            return Ok(cached.body);
         }
-        _ => origin.to_owned(),
     }
+
+    let etag = response.header("ETag").map(|x| x.to_owned());
+    let body: serde_json::Value = response.into_json()?;
+
+    if let (Some(etag), Ok(cache_file)) = (etag, cache_file) {
+        let cache = ReleaseCache {
+            etag,
+            body: body.clone(),
+        };
+        let _ = std::fs::write(cache_file, serde_json::to_string(&cache)?);
+    }
+
+    Ok(body)
 }
 
-#[derive(Default, Clone)]
-pub struct DownloadAssets {
-    windows_x86_64: Option<String>,
-    windows_x86: Option<String>,
-    macos_x86_64: Option<String>,
-    macos_arm64: Option<String>,
-    linux_x86_64: Option<String>,
+#[derive(Serialize, Deserialize)]
+struct ReleaseCache {
+    etag: String,
+    body: serde_json::Value,
 }
 
+fn get_release_cache_file(url: &str) -> Result<PathBuf> {
+    let cache_dir = get_app_dir()?.join("cache");
+    std::fs::create_dir_all(&cache_dir)?;
+
+    let name: String = url
+        .trim_start_matches("https://")
+        .chars()
+        .map(|x| if x.is_ascii_alphanumeric() { x } else { '_' })
+        .collect();
+    Ok(cache_dir.join(format!("{}.json", name)))
+}
+
+pub fn replace_github_download(origin: &str, mirror: &Mirror) -> String {
+    let path = origin.trim_start_matches("https://github.com/");
+    mirror
+        .download_template
+        .replace("{url}", origin)
+        .replace("{path}", path)
+}
+
+#[derive(Default, Clone)]
+pub struct DownloadAssets(HashMap<String, String>);
+
 impl DownloadAssets {
     pub fn to_hashmap(&self) -> HashMap<String, String> {
-        let mut result = HashMap::new();
-        if let Some(windows_x86_64) = &self.windows_x86_64 {
-            result.insert("windows_x86_64".to_owned(), windows_x86_64.clone());
-        }
-        if let Some(windows_x86) = &self.windows_x86 {
-            result.insert("windows_x86".to_owned(), windows_x86.clone());
-        }
-        if let Some(macos_arm64) = &self.macos_arm64 {
-            result.insert("macos_arm64".to_owned(), macos_arm64.clone());
-        }
-        if let Some(macos_x86_64) = &self.macos_x86_64 {
-            result.insert("macos_x86_64".to_owned(), macos_x86_64.clone());
-        }
-        if let Some(linux_x86_64) = &self.linux_x86_64 {
-            result.insert("linux_x86_64".to_owned(), linux_x86_64.clone());
-        }
-
-        result
+        self.0.clone()
     }
 
     pub fn to_order_vec(&self) -> Vec<String> {
         let os = std::env::consts::OS;
         let arch = std::env::consts::ARCH;
 
-        let mut result: Vec<String> = self.to_hashmap().keys().map(|x| x.to_owned()).collect();
+        let mut result: Vec<String> = self.0.keys().map(|x| x.to_owned()).collect();
+        result.sort();
         let os_asset = format!("{os}_{arch}");
-        
+
         for i in 0..result.len() {
             if os_asset == result[i] {
                 result.swap(0, i);
@@ -124,117 +197,34 @@ impl DownloadAssets {
     }
 }
 
-fn get_lux() -> Result<GithubLatestRelease> {
-    let owner = "iawia002".to_owned();
-    let repo = "lux".to_owned();
-
-    let response = get_github_latest(&owner, &repo)?;
-    let tag_name = jsonpath_lib::select(&response, "$.tag_name")?[0]
-        .as_str()
-        .unwrap();
-
-    let version = tag_name[1..].to_owned();
-
-    let download_assets = DownloadAssets {
-        windows_x86_64: Some(format!("https://github.com/iawia002/lux/releases/download/v{version}/lux_{version}_Windows_x86_64.zip")),
-        windows_x86: Some(format!("https://github.com/iawia002/lux/releases/download/v{version}/lux_{version}_Windows_i386.zip")),
-        macos_x86_64: Some(format!("https://github.com/iawia002/lux/releases/download/v{version}/lux_{version}_Darwin_x86_64.zip")),
-        macos_arm64: Some(format!("https://github.com/iawia002/lux/releases/download/v{version}/lux_{version}_Darwin_arm64.zip")),
-        linux_x86_64: Some(format!("https://github.com/iawia002/lux/releases/download/v{version}/lux_{version}_Linux_x86_64.zip")),
-    };
-
-    let result = GithubLatestRelease {
-        owner,
-        repo,
-        version,
-        download_assets,
-    };
-
-    Ok(result)
-}
-
-fn get_youtubedl() -> Result<GithubLatestRelease> {
-    let owner = "ytdl-org".to_owned();
-    let repo = "youtube-dl".to_owned();
-
-    let response = get_github_latest(&owner, &repo)?;
-    let tag_name = jsonpath_lib::select(&response, "$.tag_name")?[0]
-        .as_str()
-        .unwrap();
-
-    let version = tag_name.to_owned();
-
-    let download_assets = DownloadAssets {
-        windows_x86_64: Some(format!(
-            "https://github.com/ytdl-org/youtube-dl/releases/download/{version}/youtube-dl.exe"
-        )),
-        windows_x86: Some(format!(
-            "https://github.com/ytdl-org/youtube-dl/releases/download/{version}/youtube-dl.exe"
-        )),
-        ..Default::default()
-    };
-
-    let result = GithubLatestRelease {
-        owner,
-        repo,
-        version,
-        download_assets,
-    };
-
-    Ok(result)
-}
-
-fn _get_ytdlp() -> Result<GithubLatestRelease> {
-    let owner = "yt-dlp".to_owned();
-    let repo = "yt-dlp".to_owned();
+fn get_latest_release(github: &GithubSource) -> Result<GithubLatestRelease> {
+    let owner = github.owner.clone();
+    let repo = github.repo.clone();
 
     let response = get_github_latest(&owner, &repo)?;
-    let tag_name = jsonpath_lib::select(&response, "$.tag_name")?[0]
-        .as_str()
-        .unwrap();
-
-    let version = tag_name.to_owned();
-
-    let download_assets = DownloadAssets {
-        windows_x86_64: Some(
-            "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp.exe".to_owned(),
-        ),
-        windows_x86: Some(
-            "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp_x86.exe".to_owned(),
-        ),
-        macos_x86_64: Some(
-            "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp_macos".to_owned(),
-        ),
-        macos_arm64: None,
-        linux_x86_64: Some(
-            "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp".to_owned(),
-        ),
-    };
-
-    let result = GithubLatestRelease {
-        owner,
-        repo,
-        version,
-        download_assets,
-    };
-
-    Ok(result)
-}
-
-fn get_youget() -> Result<GithubLatestRelease> {
-    let owner = "soimort".to_owned();
-    let repo = "you-get".to_owned();
-
-    let response = get_github_latest(&owner, &repo)?;
-    let tag_name = jsonpath_lib::select(&response, "$.tag_name")?[0]
-        .as_str()
-        .unwrap();
-
-    let version = tag_name[1..].to_owned();
-
-    let download_assets = DownloadAssets {
-        ..Default::default()
-    };
+    let tag_name = jsonpath_lib::select(&response, "$.tag_name")?
+        .first()
+        .and_then(|x| x.as_str())
+        .ok_or_else(|| anyhow::anyhow!("No tag name in latest release"))?
+        .to_owned();
+
+    let version = tag_name
+        .strip_prefix(&github.tag_prefix)
+        .unwrap_or(&tag_name)
+        .to_owned();
+
+    let download_assets = DownloadAssets(
+        github
+            .assets
+            .iter()
+            .map(|(name, pattern)| {
+                let url = pattern
+                    .replace("{tag}", &tag_name)
+                    .replace("{version}", &version);
+                (name.clone(), url)
+            })
+            .collect(),
+    );
 
     let result = GithubLatestRelease {
         owner,
@@ -260,28 +250,48 @@ pub fn get_filename_from_url(url: &str) -> Result<String> {
 
 use std::path::Path;
 
-#[cfg(target_os = "windows")]
-pub fn extract_file_to_plugin<S: AsRef<Path>>(file_path: S) -> Result<()> {
+pub fn extract_file_to_plugin<S: AsRef<Path>>(file_path: S, program: &str, version: &str) -> Result<()> {
     use std::fs::File;
-    use crate::downloader::get_plugin_dir;
+    use crate::downloader::get_plugin_version_dir;
 
-    let plugin_dir = get_plugin_dir()?;
+    let plugin_dir = get_plugin_version_dir(program, version)?;
+    std::fs::create_dir_all(&plugin_dir)?;
     let file = File::open(file_path.as_ref())?;
     let mut archive = zip::ZipArchive::new(file)?;
 
     for i in 0..archive.len() {
         let mut file = archive.by_index(i)?;
-        if file.is_file() {
-            let mut outfile = File::create(&plugin_dir.join(file.name()))?;
-            std::io::copy(&mut file, &mut outfile)?;
+        // Entries escaping plugin dir are skipped
+        let path = match file.enclosed_name() {
+            Some(path) if file.is_file() => plugin_dir.join(path),
+            _ => continue,
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut outfile = File::create(&path)?;
+        std::io::copy(&mut file, &mut outfile)?;
+        if file.unix_mode().map(|x| x & 0o111 != 0).unwrap_or(false) {
+            set_executable(&path)?;
         }
     }
 
     Ok(())
 }
 
-#[cfg(not(target_os = "windows"))]
-pub fn extract_file_to_plugin<S: AsRef<Path>>(_file: S) -> Result<()> {
+/// Marks `path` executable, which downloaded engines need outside of Windows.
+#[cfg(unix)]
+pub fn set_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = std::fs::metadata(path)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o755);
+    std::fs::set_permissions(path, permissions)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn set_executable(_path: &Path) -> Result<()> {
     Ok(())
 }
 
@@ -295,6 +305,13 @@ mod tests {
         assert_eq!("120 B", size_to_string(120));
     }
 
+    #[test]
+    fn test_limit_to_string() {
+        assert_eq!("Unlimited", limit_to_string(0));
+        assert_eq!("256 KB/s", limit_to_string(256_000));
+        assert_eq!("2.5 MB/s", limit_to_string(2_500_000));
+    }
+
     #[test]
     fn test_percent_to_string() {
         assert_eq!("22.4%", percent_to_string(0.2242));
@@ -307,6 +324,22 @@ mod tests {
         assert_eq!(">= 1d", eta_to_string(24 * 60 * 60 + 666));
     }
 
+    #[test]
+    fn test_replace_github_download() {
+        let origin = "https://github.com/iawia002/lux/releases/download/v0.19.0/lux.zip";
+        let mirror = Mirror::from_line("kgithub https://kgithub.com/{path}").unwrap();
+        assert_eq!(
+            "https://kgithub.com/iawia002/lux/releases/download/v0.19.0/lux.zip",
+            replace_github_download(origin, &mirror)
+        );
+
+        let mirror = Mirror::from_line("ghproxy https://ghproxy.com/{url}").unwrap();
+        assert_eq!(
+            format!("https://ghproxy.com/{}", origin),
+            replace_github_download(origin, &mirror)
+        );
+    }
+
     #[test]
     fn test_get_filename_from_url() {
         let url = "https://668000.xyz/sample.html";